@@ -1,6 +1,8 @@
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use rayon::prelude::*;
+use rule_engine::batch::BatchProcessor;
 use rule_engine::engine::RuleEngine;
+use rule_engine::rule::RuleLoader;
 use rule_engine::url::{ParsedUrl, UrlParser};
 
 mod data_generator;
@@ -102,10 +104,91 @@ fn large_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+/// Single-URL latency on the large rule set: sequential index lookups versus
+/// per-part lookups on the rayon pool.
+fn single_url_latency_benchmark(c: &mut Criterion) {
+    let mut datagen = DataGenerator::new(42);
+    let engine = RuleEngine::new(datagen.generate_large_rule_set());
+    let parsed: Vec<_> = datagen
+        .generate_large_url_set()
+        .iter()
+        .take(1000)
+        .filter_map(|u| UrlParser::parse(u).ok())
+        .collect();
+
+    let mut group = c.benchmark_group("single_url_latency");
+    group.throughput(Throughput::Elements(parsed.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("sequential", |b| {
+        b.iter(|| parsed.iter().filter(|url| engine.evaluate(url).is_some()).count());
+    });
+
+    group.bench_function("parallel_parts", |b| {
+        b.iter(|| parsed.iter().filter(|url| engine.evaluate_parallel(url).is_some()).count());
+    });
+
+    group.finish();
+}
+
+/// Loading the large rule set from JSON versus the binary format.
+fn rule_loading_benchmark(c: &mut Criterion) {
+    let rules = DataGenerator::new(42).generate_large_rule_set();
+    let dir = std::env::temp_dir();
+    let json_path = dir.join(format!("rule-engine-bench-{}.json", std::process::id()));
+    let bin_path = dir.join(format!("rule-engine-bench-{}.bin", std::process::id()));
+    std::fs::write(&json_path, serde_json::to_vec(&rules).unwrap()).unwrap();
+    RuleLoader::save_bin(&rules, &bin_path).unwrap();
+
+    let mut group = c.benchmark_group("rule_loading");
+    group.throughput(Throughput::Elements(rules.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("json", |b| {
+        b.iter(|| RuleLoader::load_from_file(&json_path).unwrap());
+    });
+
+    group.bench_function("bin", |b| {
+        b.iter(|| RuleLoader::load_bin(&bin_path).unwrap());
+    });
+
+    group.finish();
+    let _ = std::fs::remove_file(&json_path);
+    let _ = std::fs::remove_file(&bin_path);
+}
+
+/// Batch processing of a URL set seen twice, with and without a parse cache
+/// large enough to hold it. The cached run parses each URL once.
+fn parse_cache_benchmark(c: &mut Criterion) {
+    let mut datagen = DataGenerator::new(42);
+    let engine = RuleEngine::new(datagen.generate_rules());
+    let urls = datagen.generate_urls();
+    let lines: Vec<String> = urls.iter().chain(&urls).cloned().collect();
+
+    let mut group = c.benchmark_group("parse_cache");
+    group.throughput(Throughput::Elements(lines.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("uncached", |b| {
+        let processor = BatchProcessor::new(&engine);
+        b.iter(|| processor.process_lines(&lines).len());
+    });
+
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            let processor = BatchProcessor::new(&engine).with_parse_cache(urls.len());
+            processor.process_lines(&lines).len()
+        });
+    });
+
+    group.finish();
+}
+
 // ---------------------------------------------------------------------------
 // harness
 // ---------------------------------------------------------------------------
 
 criterion_group!(benches, standard_benchmark);
-criterion_group!(large_benches, large_benchmark);
-criterion_main!(benches, large_benches);
+criterion_group!(large_benches, large_benchmark, single_url_latency_benchmark);
+criterion_group!(loading_benches, rule_loading_benchmark, parse_cache_benchmark);
+criterion_main!(benches, large_benches, loading_benches);
@@ -1,13 +1,20 @@
 use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
 
 const ASCII_SIZE: usize = 128;
 const NO_STATE: u32 = u32::MAX;
 
 /// Build-phase node for the Aho-Corasick automaton.
+#[derive(Serialize, Deserialize)]
 struct BuildNode<V: Clone> {
+    #[serde(with = "crate::serde_arrays::row")]
     ascii: [u32; ASCII_SIZE],
     extended: Option<HashMap<char, u32>>,
     output: Vec<V>,
+    /// Length in bytes of the prefix this node spells.
+    depth: u32,
 }
 
 /// A generic Aho-Corasick automaton for multi-pattern substring matching.
@@ -15,16 +22,25 @@ struct BuildNode<V: Clone> {
 /// Uses a DFA with array-indexed transitions for ASCII characters and a
 /// HashMap fallback for non-ASCII. After `build()`, the goto function is
 /// fully completed so search requires no failure-link chasing.
+#[derive(Serialize, Deserialize)]
 pub struct AhoCorasick<V: Clone> {
     // Build phase
     build_nodes: Option<Vec<BuildNode<V>>>,
     empty_pattern_values: Vec<V>,
     has_patterns: bool,
+    /// Patterns inserted so far, duplicates and the empty pattern included.
+    pattern_count: usize,
+    /// Wall-clock time of the last `build`/`rebuild`; not persisted.
+    #[serde(skip)]
+    last_build_duration: Option<Duration>,
 
     // Search phase (populated by build)
+    #[serde(with = "crate::serde_arrays::rows")]
     goto_table: Vec<[u32; ASCII_SIZE]>,
     extended_goto: Vec<Option<HashMap<char, u32>>>,
     output: Vec<Box<[V]>>,
+    /// Byte length of the pattern behind each `output` entry.
+    output_lens: Vec<Box<[u32]>>,
     built: bool,
 }
 
@@ -35,30 +51,90 @@ impl<V: Clone> AhoCorasick<V> {
             ascii: [NO_STATE; ASCII_SIZE],
             extended: None,
             output: Vec::new(),
+            depth: 0,
         };
         Self {
             build_nodes: Some(vec![root]),
             empty_pattern_values: Vec::new(),
             has_patterns: false,
+            pattern_count: 0,
+            last_build_duration: None,
             goto_table: Vec::new(),
             extended_goto: Vec::new(),
             output: Vec::new(),
+            output_lens: Vec::new(),
             built: false,
         }
     }
 
+    /// Creates and builds an automaton from a batch of patterns in one pass.
+    ///
+    /// Patterns are stably sorted first so that patterns sharing a prefix
+    /// are inserted consecutively. Sorting also lets the node arena be sized
+    /// exactly: each pattern adds one node per character beyond its common
+    /// prefix with the previous one. Match behavior is identical to calling
+    /// `insert` for each pattern and then `build`.
+    pub fn from_patterns(patterns: impl IntoIterator<Item = (String, V)>) -> Self {
+        let mut patterns: Vec<(String, V)> = patterns.into_iter().collect();
+        patterns.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut new_nodes = 0;
+        let mut prev = "";
+        for (pattern, _) in &patterns {
+            let shared = prev
+                .chars()
+                .zip(pattern.chars())
+                .take_while(|(a, b)| a == b)
+                .count();
+            new_nodes += pattern.chars().count() - shared;
+            prev = pattern;
+        }
+
+        let mut ac = Self::new();
+        ac.build_nodes.as_mut().unwrap().reserve_exact(new_nodes);
+        for (pattern, value) in patterns {
+            ac.insert(&pattern, value);
+        }
+        ac.build();
+        ac
+    }
+
     /// Returns `true` if no patterns have been inserted.
     pub fn is_empty(&self) -> bool {
         !self.has_patterns && self.empty_pattern_values.is_empty()
     }
 
+    /// Returns the number of patterns inserted, counting duplicates.
+    pub fn pattern_count(&self) -> usize {
+        self.pattern_count
+    }
+
+    /// Returns how long the last [`build`](Self::build) or
+    /// [`rebuild`](Self::rebuild) took, or `None` if the automaton was never
+    /// built in this process (including after deserialization).
+    pub fn last_build_duration(&self) -> Option<Duration> {
+        self.last_build_duration
+    }
+
+    /// Returns the number of automaton states, including the root.
+    pub fn state_count(&self) -> usize {
+        match &self.build_nodes {
+            Some(nodes) => nodes.len(),
+            None => self.goto_table.len(),
+        }
+    }
+
     /// Inserts a pattern with an associated value.
     ///
+    /// After [`rebuild`](Self::rebuild), new patterns become searchable at
+    /// the next `rebuild`.
+    ///
     /// # Panics
-    /// Panics if called after `build()`.
+    /// Panics if called after `build()`, which discards the build trie.
     pub fn insert(&mut self, pattern: &str, value: V) {
-        assert!(!self.built, "Cannot insert after build()");
+        assert!(self.build_nodes.is_some(), "Cannot insert after build()");
         self.has_patterns = true;
+        self.pattern_count += 1;
 
         if pattern.is_empty() {
             self.empty_pattern_values.push(value);
@@ -71,11 +147,13 @@ impl<V: Clone> AhoCorasick<V> {
             let next = Self::get_goto_build(nodes, state, c);
             if next == NO_STATE {
                 let new_id = nodes.len() as u32;
+                let depth = nodes[state as usize].depth + c.len_utf8() as u32;
                 Self::set_goto_build(nodes, state, c, new_id);
                 nodes.push(BuildNode {
                     ascii: [NO_STATE; ASCII_SIZE],
                     extended: None,
                     output: Vec::new(),
+                    depth,
                 });
                 state = new_id;
             } else {
@@ -86,24 +164,53 @@ impl<V: Clone> AhoCorasick<V> {
     }
 
     /// Constructs the automaton by computing failure links and completing the DFA.
+    ///
+    /// The build trie is discarded afterwards, so no more patterns can be
+    /// inserted. Use [`rebuild`](Self::rebuild) to keep it.
     pub fn build(&mut self) {
         let nodes = self.build_nodes.take().unwrap();
+        self.compile(&nodes);
+    }
+
+    /// Like [`build`](Self::build), but keeps the build trie so more
+    /// patterns can be inserted and the automaton rebuilt again.
+    ///
+    /// Each call reuses the trie and only recomputes failure links and the
+    /// DFA completion, at the cost of holding both representations in
+    /// memory. Match behavior is identical to a from-scratch build.
+    ///
+    /// # Panics
+    /// Panics if called after `build()`.
+    pub fn rebuild(&mut self) {
+        let nodes = self.build_nodes.take().expect("Cannot rebuild after build()");
+        self.compile(&nodes);
+        self.build_nodes = Some(nodes);
+    }
+
+    fn compile(&mut self, nodes: &[BuildNode<V>]) {
+        let started = Instant::now();
+        self.compile_tables(nodes);
+        self.last_build_duration = Some(started.elapsed());
+    }
+
+    fn compile_tables(&mut self, nodes: &[BuildNode<V>]) {
         let state_count = nodes.len();
 
         // Copy to mutable search-phase structures
         let mut goto: Vec<[u32; ASCII_SIZE]> = nodes.iter().map(|n| n.ascii).collect();
         let mut extended: Vec<Option<HashMap<char, u32>>> =
             nodes.iter().map(|n| n.extended.clone()).collect();
-        let mut output: Vec<Vec<V>> = nodes.into_iter().map(|n| n.output).collect();
+        let mut output: Vec<Vec<V>> = nodes.iter().map(|n| n.output.clone()).collect();
+        let mut lens: Vec<Vec<u32>> = nodes.iter().map(|n| vec![n.depth; n.output.len()]).collect();
 
         let mut failure = vec![0u32; state_count];
         let mut queue = VecDeque::new();
 
         // Phase 1: init depth-1 states
-        for c in 0..ASCII_SIZE {
-            let child = goto[0][c];
+        for slot in goto[0].iter_mut() {
+            let child = *slot;
             if child == NO_STATE {
-                goto[0][c] = 0; // self-loop on root
+                *slot = 0; // self-loop on root
             } else {
                 failure[child as usize] = 0;
                 queue.push_back(child);
@@ -125,7 +232,7 @@ impl<V: Clone> AhoCorasick<V> {
                 if child != NO_STATE {
                     let f = Self::follow_failure(&goto, &extended, &failure, current, c as u8 as char);
                     failure[child as usize] = f;
-                    Self::merge_output(&mut output, child as usize, f as usize);
+                    Self::merge_output(&mut output, &mut lens, child as usize, f as usize);
                     queue.push_back(child);
                 }
             }
@@ -134,7 +241,7 @@ impl<V: Clone> AhoCorasick<V> {
                 for (&c, &child) in &ext {
                     let f = Self::follow_failure(&goto, &extended, &failure, current, c);
                     failure[child as usize] = f;
-                    Self::merge_output(&mut output, child as usize, f as usize);
+                    Self::merge_output(&mut output, &mut lens, child as usize, f as usize);
                     queue.push_back(child);
                 }
             }
@@ -142,8 +249,7 @@ impl<V: Clone> AhoCorasick<V> {
 
         // Phase 3: complete DFA
         // Seed with root's children
-        for c in 0..ASCII_SIZE {
-            let child = goto[0][c];
+        for &child in &goto[0] {
             if child != 0 {
                 queue.push_back(child);
             }
@@ -158,11 +264,12 @@ impl<V: Clone> AhoCorasick<V> {
             let cur = current as usize;
             let fail = failure[cur] as usize;
 
-            for c in 0..ASCII_SIZE {
-                if goto[cur][c] == NO_STATE {
-                    goto[cur][c] = goto[fail][c]; // inherit from failure
+            let fail_row = goto[fail];
+            for (slot, &inherited) in goto[cur].iter_mut().zip(&fail_row) {
+                if *slot == NO_STATE {
+                    *slot = inherited; // inherit from failure
                 } else {
-                    queue.push_back(goto[cur][c]);
+                    queue.push_back(*slot);
                 }
             }
 
@@ -187,6 +294,7 @@ impl<V: Clone> AhoCorasick<V> {
         self.goto_table = goto;
         self.extended_goto = extended;
         self.output = output.into_iter().map(|v| v.into_boxed_slice()).collect();
+        self.output_lens = lens.into_iter().map(|v| v.into_boxed_slice()).collect();
         self.built = true;
     }
 
@@ -209,6 +317,80 @@ impl<V: Clone> AhoCorasick<V> {
         }
     }
 
+    /// Like [`search`](Self::search), but invokes the callback at most `max`
+    /// times, bounding work on inputs crafted to hit many overlapping
+    /// patterns.
+    ///
+    /// Returns `true` only if matches were dropped, i.e. the callback would
+    /// have fired again after its `max`-th call. Text with exactly `max`
+    /// matches returns `false`. Once the limit is reached, the scan goes on
+    /// without callbacks only until the next match.
+    pub fn search_limited(&self, text: &str, max: usize, callback: &mut impl FnMut(&V)) -> bool {
+        debug_assert!(self.built, "Must call build() before search_limited()");
+
+        let mut remaining = max;
+        // Returns `true` on a match the limit leaves no call for.
+        let mut emit = |values: &[V]| -> bool {
+            for v in values {
+                if remaining == 0 {
+                    return true;
+                }
+                callback(v);
+                remaining -= 1;
+            }
+            false
+        };
+
+        if emit(&self.empty_pattern_values) {
+            return true;
+        }
+        let mut state = 0u32;
+        for c in text.chars() {
+            state = self.next_state(state, c);
+            if emit(&self.output[state as usize]) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Reports non-overlapping matches, scanning left to right: at each
+    /// position only the longest match starting there is kept, and the scan
+    /// resumes after it.
+    ///
+    /// Values of patterns identical to the chosen one are all reported.
+    /// Empty patterns never match. All matches are collected before
+    /// selection, so this allocates in proportion to the overlapping match
+    /// count.
+    pub fn search_leftmost_longest(&self, text: &str, callback: &mut impl FnMut(&V)) {
+        debug_assert!(self.built, "Must call build() before search_leftmost_longest()");
+
+        // (start, end, value) for every overlapping match.
+        let mut spans: Vec<(usize, usize, &V)> = Vec::new();
+        let mut state = 0u32;
+        for (i, c) in text.char_indices() {
+            state = self.next_state(state, c);
+            let end = i + c.len_utf8();
+            let s = state as usize;
+            for (v, &len) in self.output[s].iter().zip(&*self.output_lens[s]) {
+                spans.push((end - len as usize, end, v));
+            }
+        }
+        spans.sort_by_key(|&(start, end, _)| (start, std::cmp::Reverse(end)));
+
+        let mut pos = 0;
+        let mut chosen = None;
+        for (start, end, v) in spans {
+            if chosen == Some((start, end)) {
+                callback(v);
+            } else if start >= pos {
+                callback(v);
+                chosen = Some((start, end));
+                pos = end;
+            }
+        }
+    }
+
     /// Byte-oriented search. Iterates `text.as_bytes()` directly, using
     /// the goto table for bytes < 128 and resetting to state 0 for
     /// bytes >= 128 (safe since all patterns are ASCII).
@@ -292,12 +474,19 @@ impl<V: Clone> AhoCorasick<V> {
         }
     }
 
-    fn merge_output(output: &mut [Vec<V>], state: usize, fail_state: usize) {
+    fn merge_output(
+        output: &mut [Vec<V>],
+        lens: &mut [Vec<u32>],
+        state: usize,
+        fail_state: usize,
+    ) {
         if output[fail_state].is_empty() {
             return;
         }
         let fail_out = output[fail_state].clone();
         output[state].extend(fail_out);
+        let fail_lens = lens[fail_state].clone();
+        lens[state].extend(fail_lens);
     }
 
     fn next_state(&self, state: u32, c: char) -> u32 {
@@ -515,6 +704,37 @@ mod tests {
         assert!(AhoCorasick::<u32>::new().is_empty());
     }
 
+    #[test]
+    fn state_count_is_stable_across_build() {
+        let mut ac = AhoCorasick::new();
+        assert_eq!(1, ac.state_count());
+        ac.insert("he", 1u32);
+        ac.insert("hers", 2u32);
+        assert_eq!(5, ac.state_count());
+        ac.build();
+        assert_eq!(5, ac.state_count());
+    }
+
+    #[test]
+    fn pattern_count_and_build_duration() {
+        let patterns = ["he", "she", "his", "hers", "he", ""];
+        let mut ac = AhoCorasick::new();
+        for (i, p) in patterns.iter().enumerate() {
+            ac.insert(p, i as u32);
+        }
+        assert_eq!(patterns.len(), ac.pattern_count());
+        assert!(ac.last_build_duration().is_none());
+
+        // Shared prefixes collapse, so states fall between the longest
+        // pattern and the total character count (plus the root).
+        let total_chars: usize = patterns.iter().map(|p| p.len()).sum();
+        let longest = patterns.iter().map(|p| p.len()).max().unwrap();
+        ac.build();
+        assert!(ac.state_count() > longest);
+        assert!(ac.state_count() <= total_chars + 1);
+        assert!(ac.last_build_duration().is_some());
+    }
+
     #[test]
     fn int_is_not_empty_after_insert() {
         let mut ac = AhoCorasick::new();
@@ -577,6 +797,42 @@ mod tests {
         assert!(result.contains(&7));
     }
 
+    #[test]
+    fn from_patterns_matches_incremental_build() {
+        let patterns = [
+            ("hers", 4u32),
+            ("he", 1),
+            ("his", 3),
+            ("she", 2),
+            ("he", 5),
+            ("", 6),
+            ("\u{00E9}t\u{00E9}", 7),
+        ];
+
+        let mut incremental = AhoCorasick::new();
+        for (p, v) in patterns {
+            incremental.insert(p, v);
+        }
+        incremental.build();
+        let bulk = AhoCorasick::from_patterns(patterns.iter().map(|&(p, v)| (p.to_string(), v)));
+
+        assert_eq!(incremental.state_count(), bulk.state_count());
+        for text in ["shers", "ushers his", "", "nothing", "un \u{00E9}t\u{00E9} chaud", "hehe"] {
+            let mut expected = search_u32(&incremental, text);
+            let mut actual = search_u32(&bulk, text);
+            expected.sort_unstable();
+            actual.sort_unstable();
+            assert_eq!(expected, actual, "mismatch on {:?}", text);
+        }
+    }
+
+    #[test]
+    fn from_patterns_empty_is_built_and_empty() {
+        let ac = AhoCorasick::<u32>::from_patterns(Vec::new());
+        assert!(ac.is_empty());
+        assert!(search_u32(&ac, "text").is_empty());
+    }
+
     #[test]
     fn search_bytes_finds_single_pattern() {
         let mut ac = AhoCorasick::new();
@@ -613,4 +869,109 @@ mod tests {
         ac.search_bytes("anything", &mut |v| result.push(*v));
         assert!(result.contains(&42));
     }
+
+    #[test]
+    fn search_limited_stops_at_max() {
+        let mut ac = AhoCorasick::new();
+        for (i, p) in ["a", "aa", "aaa", "aaaa"].iter().enumerate() {
+            ac.insert(p, i as u32);
+        }
+        ac.build();
+
+        let text = "a".repeat(10_000);
+        let mut calls = 0;
+        let hit = ac.search_limited(&text, 100, &mut |_| calls += 1);
+        assert!(hit);
+        assert_eq!(100, calls);
+    }
+
+    #[test]
+    fn search_limited_below_max_matches_search() {
+        let mut ac = AhoCorasick::new();
+        ac.insert("he", 1u32);
+        ac.insert("she", 2);
+        ac.build();
+
+        let mut limited = Vec::new();
+        let hit = ac.search_limited("ushers", 10, &mut |v| limited.push(*v));
+        assert!(!hit);
+        assert_eq!(ac.search_collect("ushers"), limited);
+    }
+
+    #[test]
+    fn search_limited_reports_only_dropped_matches() {
+        let mut ac = AhoCorasick::new();
+        ac.insert("he", 1u32);
+        ac.insert("she", 2);
+        ac.build();
+
+        // "ushers" has exactly two matches: none is dropped.
+        let mut calls = 0;
+        assert!(!ac.search_limited("ushers", 2, &mut |_| calls += 1));
+        assert_eq!(2, calls);
+        assert!(ac.search_limited("ushers", 1, &mut |_| calls += 1));
+        assert_eq!(3, calls);
+
+        assert!(!ac.search_limited("xyz", 0, &mut |_| calls += 1));
+        assert!(ac.search_limited("he", 0, &mut |_| calls += 1));
+        assert_eq!(3, calls);
+    }
+
+    #[test]
+    fn rebuild_accepts_patterns_across_cycles() {
+        let mut ac = AhoCorasick::new();
+        ac.insert("he", 1u32);
+        ac.insert("she", 2);
+        ac.rebuild();
+        assert_eq!(vec![2, 1], ac.search_collect("she"));
+
+        ac.insert("his", 3);
+        ac.insert("hers", 4);
+        ac.insert("caf\u{00E9}", 5);
+        ac.rebuild();
+
+        let mut scratch = AhoCorasick::new();
+        for (p, v) in [("he", 1u32), ("she", 2), ("his", 3), ("hers", 4), ("caf\u{00E9}", 5)] {
+            scratch.insert(p, v);
+        }
+        scratch.build();
+
+        for text in ["ushers", "this", "she", "un caf\u{00E9}", "nothing"] {
+            let mut incremental = ac.search_collect(text);
+            let mut expected = scratch.search_collect(text);
+            incremental.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(expected, incremental, "text {:?}", text);
+        }
+        assert_eq!(scratch.state_count(), ac.state_count());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot insert after build()")]
+    fn insert_after_build_panics() {
+        let mut ac = AhoCorasick::new();
+        ac.insert("a", 1u32);
+        ac.build();
+        ac.insert("b", 2);
+    }
+
+    #[test]
+    fn leftmost_longest_skips_overlaps() {
+        let ac = AhoCorasick::from_patterns(
+            [("a", 1u32), ("ab", 2), ("abc", 3)].map(|(p, v)| (p.to_string(), v)),
+        );
+        let mut result = Vec::new();
+        ac.search_leftmost_longest("abcab", &mut |v| result.push(*v));
+        assert_eq!(vec![3, 2], result);
+    }
+
+    #[test]
+    fn leftmost_longest_prefers_earlier_start() {
+        let ac = AhoCorasick::from_patterns(
+            [("bcd", 1u32), ("ab", 2), ("caf\u{00E9}", 3)].map(|(p, v)| (p.to_string(), v)),
+        );
+        let mut result = Vec::new();
+        ac.search_leftmost_longest("abcd caf\u{00E9}", &mut |v| result.push(*v));
+        assert_eq!(vec![2, 3], result);
+    }
 }
@@ -1,64 +1,519 @@
 use crate::engine::RuleEngine;
-use crate::url::UrlParser;
+use crate::error::Result;
+use crate::parse_cache::ParseCache;
+use crate::url::{ParsedUrl, UrlParseError, UrlParser};
 use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fs;
-use std::io;
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::Path;
 
+/// Outcome category of evaluating a single URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStatus {
+    Matched,
+    NoMatch,
+    InvalidUrl,
+}
+
 /// The result of evaluating a single URL.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// `result` holds the matched rule's result, or the `NO_MATCH` /
+/// `INVALID_URL` sentinel matching `status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct UrlResult {
     pub url: String,
     pub result: String,
+    pub status: MatchStatus,
+}
+
+impl UrlResult {
+    /// Serializes this result as a single-line JSON object with `url`,
+    /// `result` and `status` fields.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("UrlResult is always serializable")
+    }
+}
+
+/// Every rule matching a single URL, for multi-match evaluation.
+///
+/// `rules` holds the names of the matching rules in the order `evaluate`
+/// checks them; it is empty for `NoMatch` and `InvalidUrl`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UrlMatches {
+    pub url: String,
+    pub rules: Vec<String>,
+    pub status: MatchStatus,
+}
+
+/// Counters of a [`BatchProcessor`]'s parse cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseCacheStats {
+    /// Lines whose parse result came from the cache.
+    pub hits: u64,
+    /// Lines that had to be parsed.
+    pub misses: u64,
+    /// Entries currently cached.
+    pub len: usize,
 }
 
 /// Processes batches of URLs against a RuleEngine.
 pub struct BatchProcessor<'a> {
     engine: &'a RuleEngine,
+    pool: Option<rayon::ThreadPool>,
+    /// Lines starting with this (after leading whitespace) are skipped.
+    comment_prefix: Option<String>,
+    parse_cache: Option<ParseCache>,
 }
 
 impl<'a> BatchProcessor<'a> {
-    /// Creates a batch processor backed by the given engine.
+    /// Creates a batch processor backed by the given engine, using the
+    /// global rayon pool.
     pub fn new(engine: &'a RuleEngine) -> Self {
-        Self { engine }
+        Self {
+            engine,
+            pool: None,
+            comment_prefix: None,
+            parse_cache: None,
+        }
+    }
+
+    /// Creates a batch processor that runs on its own rayon pool of
+    /// `threads` workers, bounding parallelism independently of the
+    /// global pool.
+    ///
+    /// # Panics
+    /// Panics if the thread pool cannot be created.
+    pub fn with_threads(engine: &'a RuleEngine, threads: usize) -> Self {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("failed to build rayon thread pool");
+        Self {
+            engine,
+            pool: Some(pool),
+            comment_prefix: None,
+            parse_cache: None,
+        }
+    }
+
+    /// Skips lines starting with `prefix` (ignoring leading whitespace) as
+    /// comments, e.g. `#` for the URL files used in tests.
+    pub fn with_comment_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.comment_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Caches the parse results of up to `capacity` distinct lines, least
+    /// recently used evicted first, so URLs recurring across batches are
+    /// parsed once.
+    ///
+    /// The cache is keyed by the trimmed line and shared by all workers.
+    /// It serves [`process_lines`](Self::process_lines),
+    /// [`process_lines_iter`](Self::process_lines_iter) and the file
+    /// methods; a hit saves the parse but not the evaluation. Short ASCII
+    /// URLs parse about as fast as a lookup, so this pays off mainly for
+    /// long or internationalized URLs.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn with_parse_cache(mut self, capacity: usize) -> Self {
+        self.parse_cache = Some(ParseCache::new(capacity));
+        self
+    }
+
+    /// Returns the parse cache's hit and miss counts, or `None` when built
+    /// without [`with_parse_cache`](Self::with_parse_cache).
+    pub fn parse_cache_stats(&self) -> Option<ParseCacheStats> {
+        self.parse_cache.as_ref().map(ParseCache::stats)
+    }
+
+    /// Preallocates the engine's thread-local query buffers on every worker
+    /// of the pool this processor runs on, so the first batch does not pay
+    /// for growing them. Returns the number of workers warmed.
+    pub fn warmup_pool(&self) -> usize {
+        let warm = |_: rayon::BroadcastContext<'_>| self.engine.warmup_thread_local();
+        match &self.pool {
+            Some(pool) => pool.broadcast(warm).len(),
+            None => rayon::broadcast(warm).len(),
+        }
     }
 
     /// Reads URLs from a file and evaluates each against the engine.
-    pub fn process_file(&self, url_file: &Path) -> io::Result<Vec<UrlResult>> {
+    pub fn process_file(&self, url_file: &Path) -> Result<Vec<UrlResult>> {
         let content = fs::read_to_string(url_file)?;
         let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
         Ok(self.process_lines(&lines))
     }
 
+    /// Streams URLs from a file `every` lines at a time, calling `progress`
+    /// with the running line count after each chunk, a trailing partial one
+    /// included.
+    ///
+    /// Counts every line read, including blank and comment lines that
+    /// produce no result, so the last call reports the file's line count.
+    /// An empty file gets no call. Only one chunk of input is held in memory
+    /// at a time.
+    ///
+    /// # Panics
+    /// Panics if `every` is zero.
+    pub fn process_file_with_progress(
+        &self,
+        url_file: &Path,
+        every: usize,
+        progress: &mut impl FnMut(usize),
+    ) -> Result<Vec<UrlResult>> {
+        assert!(every > 0, "progress interval must be positive");
+        let mut lines = BufReader::new(fs::File::open(url_file)?).lines();
+        let mut chunk = Vec::with_capacity(every);
+        let mut results = Vec::new();
+        let mut seen = 0;
+        loop {
+            chunk.clear();
+            for line in lines.by_ref().take(every) {
+                chunk.push(line?);
+            }
+            if chunk.is_empty() {
+                return Ok(results);
+            }
+            results.extend(self.process_lines(&chunk));
+            seen += chunk.len();
+            progress(seen);
+            if chunk.len() < every {
+                return Ok(results);
+            }
+        }
+    }
+
     /// Evaluates a list of URL strings against the engine in parallel.
     ///
-    /// Uses rayon parallel iterator for distribution across available cores.
+    /// Uses rayon parallel iterator for distribution across available cores,
+    /// or across the dedicated pool when built with `with_threads`.
     /// Encounter order is preserved.
     pub fn process_lines(&self, lines: &[String]) -> Vec<UrlResult> {
+        self.install(|| {
+            lines
+                .par_iter()
+                .filter(|line| !self.is_skipped(line))
+                .map(|line| self.evaluate_line(line))
+                .collect()
+        })
+    }
+
+    /// Evaluates URL strings like [`process_lines`](Self::process_lines),
+    /// but lazily on the calling thread, one line per `next()`.
+    ///
+    /// Results can be streamed out without holding them all, at the cost of
+    /// parallelism: throughput is that of a single core, and the dedicated
+    /// pool from `with_threads` is not used.
+    pub fn process_lines_iter<'b>(
+        &'b self,
+        lines: &'b [String],
+    ) -> impl Iterator<Item = UrlResult> + 'b {
         lines
-            .par_iter()
-            .filter(|line| !line.trim().is_empty())
+            .iter()
+            .filter(|line| !self.is_skipped(line))
             .map(|line| self.evaluate_line(line))
+    }
+
+    /// Evaluates URL strings like [`process_lines`](Self::process_lines),
+    /// and also returns why each `INVALID_URL` line failed to parse.
+    ///
+    /// Errors are paired with the index of their line in `lines` (skipped
+    /// lines included), in input order; offsets are into that line as given.
+    /// URLs the engine [`rejects`](RuleEngine::rejects) parse fine, so they
+    /// are `INVALID_URL` without an error entry.
+    pub fn process_lines_detailed(
+        &self,
+        lines: &[String],
+    ) -> (Vec<UrlResult>, Vec<(usize, UrlParseError)>) {
+        let (results, errors): (Vec<UrlResult>, Vec<Option<(usize, UrlParseError)>>) =
+            self.install(|| {
+                lines
+                    .par_iter()
+                    .enumerate()
+                    .filter(|(_, line)| !self.is_skipped(line))
+                    .map(|(i, line)| {
+                        let stripped = line.trim();
+                        match UrlParser::parse_detailed(line) {
+                            Ok(parsed) if !self.engine.rejects(&parsed) => {
+                                (Self::evaluated(stripped, self.engine.evaluate(&parsed)), None)
+                            }
+                            Ok(_) => (Self::invalid(stripped), None),
+                            Err(e) => (Self::invalid(stripped), Some((i, e))),
+                        }
+                    })
+                    .unzip()
+            });
+        (results, errors.into_iter().flatten().collect())
+    }
+
+    /// Evaluates URL strings like [`process_lines`](Self::process_lines),
+    /// but reports every matching rule per URL via
+    /// [`RuleEngine::evaluate_all`].
+    pub fn process_lines_all(&self, lines: &[String]) -> Vec<UrlMatches> {
+        self.install(|| {
+            lines
+                .par_iter()
+                .filter(|line| !self.is_skipped(line))
+                .map(|line| {
+                    let stripped = line.trim();
+                    let (rules, status) = match self.parse(stripped) {
+                        Some(parsed) => {
+                            let rules: Vec<String> = self
+                                .engine
+                                .evaluate_all(&parsed)
+                                .iter()
+                                .map(|rule| rule.name.clone())
+                                .collect();
+                            let status = if rules.is_empty() {
+                                MatchStatus::NoMatch
+                            } else {
+                                MatchStatus::Matched
+                            };
+                            (rules, status)
+                        }
+                        None => (Vec::new(), MatchStatus::InvalidUrl),
+                    };
+                    UrlMatches {
+                        url: stripped.to_string(),
+                        rules,
+                        status,
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Runs `op` on this processor's pool, or the global one.
+    fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// Evaluates URL strings like [`process_lines`](Self::process_lines), but
+    /// evaluates each distinct `ParsedUrl` only once.
+    ///
+    /// Lines are deduplicated after parsing, so different raw strings that
+    /// parse to the same URL (e.g. differing only in host case) share one
+    /// evaluation. Every line still gets its own result, in input order.
+    pub fn process_lines_dedup(&self, lines: &[String]) -> Vec<UrlResult> {
+        self.process_lines_dedup_with(lines, |url| self.engine.evaluate(url))
+    }
+
+    fn process_lines_dedup_with<'e, F>(&self, lines: &[String], evaluate: F) -> Vec<UrlResult>
+    where
+        F: Fn(&ParsedUrl) -> Option<&'e str> + Sync,
+    {
+        self.install(|| {
+            let parsed: Vec<(&str, Option<ParsedUrl>)> = lines
+                .par_iter()
+                .filter(|line| !self.is_skipped(line))
+                .map(|line| {
+                    let stripped = line.trim();
+                    (stripped, self.parse(stripped))
+                })
+                .collect();
+
+            // Position of each line's URL in `unique`, or None if invalid.
+            let mut unique: Vec<&ParsedUrl> = Vec::new();
+            let mut seen: HashMap<&ParsedUrl, usize> = HashMap::new();
+            let slots: Vec<Option<usize>> = parsed
+                .iter()
+                .map(|(_, url)| {
+                    url.as_ref().map(|url| {
+                        *seen.entry(url).or_insert_with(|| {
+                            unique.push(url);
+                            unique.len() - 1
+                        })
+                    })
+                })
+                .collect();
+
+            let outcomes: Vec<Option<&str>> = unique.par_iter().map(|url| evaluate(url)).collect();
+
+            parsed
+                .iter()
+                .zip(slots)
+                .map(|(&(url, _), slot)| match slot {
+                    Some(i) => Self::evaluated(url, outcomes[i]),
+                    None => Self::invalid(url),
+                })
+                .collect()
+        })
+    }
+
+    /// Returns `true` for blank lines and, when configured, comment lines.
+    fn is_skipped(&self, line: &str) -> bool {
+        let trimmed = line.trim();
+        trimmed.is_empty()
+            || self
+                .comment_prefix
+                .as_deref()
+                .is_some_and(|prefix| trimmed.starts_with(prefix))
+    }
+
+    /// Evaluates URL strings like [`process_lines`](Self::process_lines) and
+    /// renders each result as a JSON Lines record.
+    pub fn process_lines_json(&self, lines: &[String]) -> Vec<String> {
+        self.process_lines(lines)
+            .iter()
+            .map(UrlResult::to_json)
             .collect()
     }
 
+    /// Writes results as RFC 4180 CSV with a `url,result` header row.
+    ///
+    /// Fields containing commas, double quotes or line breaks are quoted,
+    /// with embedded quotes doubled. Records end with CRLF.
+    pub fn write_csv<W: Write>(&self, results: &[UrlResult], w: &mut W) -> io::Result<()> {
+        w.write_all(b"url,result\r\n")?;
+        for r in results {
+            write_csv_field(w, &r.url)?;
+            w.write_all(b",")?;
+            write_csv_field(w, &r.result)?;
+            w.write_all(b"\r\n")?;
+        }
+        Ok(())
+    }
+
     fn evaluate_line(&self, line: &str) -> UrlResult {
+        self.evaluate_line_with(line, UrlParser::parse_detailed)
+    }
+
+    fn evaluate_line_with(
+        &self,
+        line: &str,
+        parse: impl FnOnce(&str) -> std::result::Result<ParsedUrl, UrlParseError>,
+    ) -> UrlResult {
         let stripped = line.trim();
-        match UrlParser::parse(stripped) {
-            Ok(parsed) => {
-                let result = match self.engine.evaluate(&parsed) {
-                    Some(r) => r.to_string(),
-                    None => "NO_MATCH".to_string(),
-                };
-                UrlResult {
-                    url: stripped.to_string(),
-                    result,
-                }
+        let outcome = |parsed: &ParsedUrl| {
+            if self.engine.rejects(parsed) {
+                Self::invalid(stripped)
+            } else {
+                Self::evaluated(stripped, self.engine.evaluate(parsed))
             }
-            Err(_) => UrlResult {
-                url: stripped.to_string(),
-                result: "INVALID_URL".to_string(),
+        };
+        match &self.parse_cache {
+            Some(cache) => match cache.get_or_parse(stripped, parse) {
+                Ok(parsed) => outcome(&parsed),
+                Err(_) => Self::invalid(stripped),
+            },
+            None => match parse(stripped) {
+                Ok(parsed) => outcome(&parsed),
+                Err(_) => Self::invalid(stripped),
             },
         }
     }
+
+    /// Parses a stripped line, treating URLs the engine
+    /// [`rejects`](RuleEngine::rejects) as invalid too.
+    fn parse(&self, url: &str) -> Option<ParsedUrl> {
+        UrlParser::parse(url).ok().filter(|parsed| !self.engine.rejects(parsed))
+    }
+
+    fn evaluated(url: &str, outcome: Option<&str>) -> UrlResult {
+        let (result, status) = match outcome {
+            Some(r) => (r.to_string(), MatchStatus::Matched),
+            None => ("NO_MATCH".to_string(), MatchStatus::NoMatch),
+        };
+        UrlResult {
+            url: url.to_string(),
+            result,
+            status,
+        }
+    }
+
+    fn invalid(url: &str) -> UrlResult {
+        UrlResult {
+            url: url.to_string(),
+            result: "INVALID_URL".to_string(),
+            status: MatchStatus::InvalidUrl,
+        }
+    }
+}
+
+fn write_csv_field<W: Write>(w: &mut W, field: &str) -> io::Result<()> {
+    if field.contains([',', '"', '\n', '\r']) {
+        write!(w, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        w.write_all(field.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::{Condition, Operator, Rule, UrlPart};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn dedup_evaluates_each_distinct_url_once() {
+        let rule = Rule::new(
+            "a",
+            1,
+            vec![Condition::new(UrlPart::Host, Operator::Equals, "a.com", false)],
+            "hit",
+        );
+        let engine = RuleEngine::new(vec![rule]);
+        let processor = BatchProcessor::new(&engine);
+        let lines: Vec<String> = ["https://a.com/x", "https://A.com/x", "://bad", "https://a.com/x"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let calls = AtomicUsize::new(0);
+        let results = processor.process_lines_dedup_with(&lines, |url| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            engine.evaluate(url)
+        });
+
+        assert_eq!(1, calls.load(Ordering::Relaxed));
+        assert_eq!(4, results.len());
+        assert_eq!("https://A.com/x", results[1].url);
+        assert_eq!(MatchStatus::InvalidUrl, results[2].status);
+        for i in [0, 1, 3] {
+            assert_eq!("hit", results[i].result);
+        }
+        assert_eq!(processor.process_lines(&lines), processor.process_lines_dedup(&lines));
+    }
+
+    #[test]
+    fn parse_cache_parses_each_distinct_line_once() {
+        let rule = Rule::new(
+            "a",
+            1,
+            vec![Condition::new(UrlPart::Host, Operator::Equals, "a.com", false)],
+            "hit",
+        );
+        let engine = RuleEngine::new(vec![rule]);
+        let processor = BatchProcessor::new(&engine).with_parse_cache(16);
+        let lines = ["https://a.com/x", " https://a.com/x", "://bad", "https://b.com/", "://bad"];
+
+        let parses = AtomicUsize::new(0);
+        let results: Vec<UrlResult> = lines
+            .iter()
+            .cycle()
+            .take(lines.len() * 3)
+            .map(|line| {
+                processor.evaluate_line_with(line, |url| {
+                    parses.fetch_add(1, Ordering::Relaxed);
+                    UrlParser::parse_detailed(url)
+                })
+            })
+            .collect();
+
+        assert_eq!(3, parses.load(Ordering::Relaxed));
+        let stats = processor.parse_cache_stats().unwrap();
+        assert_eq!((12, 3, 3), (stats.hits, stats.misses, stats.len));
+        let uncached = BatchProcessor::new(&engine);
+        for (line, result) in lines.iter().cycle().zip(&results) {
+            assert_eq!(&uncached.evaluate_line(line), result);
+        }
+        assert_eq!(None, uncached.parse_cache_stats());
+    }
 }
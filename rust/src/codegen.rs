@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::rule::{Condition, Operator, Rule, UrlPart};
+use crate::url::HostCase;
+
+/// Renders `rules`, already in evaluation order, as the source of a Rust
+/// function `match_url` returning the first matching rule's result,
+/// preceded by a `static` for each distinct glob or host pattern it uses.
+///
+/// The generated code refers to this crate by its `rule_engine` path.
+pub(crate) fn rust_matcher<'r>(
+    rules: impl IntoIterator<Item = &'r Rule>,
+    canonical_query: bool,
+    host_case: HostCase,
+) -> String {
+    let mut patterns = Patterns::default();
+    let mut out = String::new();
+    out.push_str("pub fn match_url(url: &rule_engine::url::ParsedUrl) -> Option<&'static str> {\n");
+    if canonical_query {
+        out.push_str(
+            "    let url = &rule_engine::url::ParsedUrl {\n        \
+             query: rule_engine::url::canonical_query(&url.query).into_owned(),\n        \
+             ..url.clone()\n    };\n",
+        );
+    }
+    for rule in rules {
+        let result = literal(&rule.result);
+        let name = rule.name.replace(['\n', '\r'], " ");
+        let declared = patterns.clone();
+        let mut checks: Vec<String> = rule
+            .conditions
+            .iter()
+            .map(|c| condition_expr(c, host_case, &mut patterns))
+            .collect();
+        if checks.iter().any(|check| check == "false") {
+            // Drop the patterns of a rule that is left out, unused.
+            patterns = declared;
+            continue;
+        }
+        checks.retain(|check| check != "true");
+        match checks.as_slice() {
+            [] => {
+                // A rule without checks always matches, so nothing after it can.
+                let _ = writeln!(out, "    // {name}\n    Some({result})\n}}");
+                return patterns.prepend_to(out);
+            }
+            [check] => {
+                let _ = writeln!(out, "    // {name}\n    if {check} {{");
+            }
+            [first, rest @ ..] => {
+                let _ = writeln!(out, "    // {name}\n    if {first}");
+                for check in rest {
+                    let _ = writeln!(out, "        && {check}");
+                }
+                out.push_str("    {\n");
+            }
+        }
+        let _ = writeln!(out, "        return Some({result});\n    }}");
+    }
+    out.push_str("    None\n}\n");
+    patterns.prepend_to(out)
+}
+
+/// Glob and host patterns used by a generated matcher, each compiled once
+/// into a lazily initialized `static` rather than on every call.
+#[derive(Clone, Default)]
+struct Patterns {
+    declarations: String,
+    /// `(type, pattern)` -> name of the static holding it.
+    names: HashMap<(&'static str, String), String>,
+}
+
+impl Patterns {
+    /// Returns the name of the static holding `pattern` compiled as
+    /// `rule_engine::glob::{ty}`, declaring it on first use.
+    fn static_name(&mut self, ty: &'static str, pattern: &str) -> String {
+        let next = self.names.len();
+        let declarations = &mut self.declarations;
+        self.names
+            .entry((ty, pattern.to_string()))
+            .or_insert_with(|| {
+                let name = format!("PATTERN_{next}");
+                let _ = writeln!(
+                    declarations,
+                    "static {name}: std::sync::LazyLock<rule_engine::glob::{ty}> =\n    \
+                     std::sync::LazyLock::new(|| rule_engine::glob::{ty}::new({}));",
+                    literal(pattern)
+                );
+                name
+            })
+            .clone()
+    }
+
+    /// Puts the declarations, if any, above the generated function.
+    fn prepend_to(self, function: String) -> String {
+        if self.declarations.is_empty() {
+            function
+        } else {
+            format!("{}\n{function}", self.declarations)
+        }
+    }
+}
+
+/// Returns a boolean Rust expression that holds when the condition does.
+///
+/// Conditions that always or never hold come out as `true` or `false`.
+fn condition_expr(cond: &Condition, host_case: HostCase, patterns: &mut Patterns) -> String {
+    let cond = &*cond.normalized(host_case);
+    let v = part_expr(cond.part, cond.decoded);
+    if let Some(param) = &cond.param {
+        // The same pairs the engine sees, see `ParsedUrl::param_pairs`.
+        let expr = format!(
+            "url.param_pairs({})\
+             .any(|(key, value)| key == {} && {{ let value: &str = &value; {} }})",
+            cond.decoded,
+            literal(param),
+            operator_expr(cond, "value", patterns)
+        );
+        return if cond.negated { format!("!{expr}") } else { expr };
+    }
+    let other = (cond.operator == Operator::EqualsPart)
+        .then(|| UrlPart::from_name(&cond.value))
+        .flatten();
+    match (cond.negated, cond.operator) {
+        (true, Operator::Equals) => format!("{v} != {}", literal(&cond.value)),
+        (true, Operator::EqualsPart) if let Some(other) = other => {
+            format!("{v} != {}", part_expr(other, cond.decoded))
+        }
+        (true, _) => match operator_expr(cond, &v, patterns).as_str() {
+            "true" => "false".to_string(),
+            "false" => "true".to_string(),
+            // Compound expressions come parenthesized already.
+            expr if expr.starts_with('(') => format!("!{expr}"),
+            expr => format!("!({expr})"),
+        },
+        (false, _) => operator_expr(cond, &v, patterns),
+    }
+}
+
+/// Returns a `&str` expression for the part's value.
+fn part_expr(part: UrlPart, decoded: bool) -> String {
+    let decodable = matches!(part, UrlPart::Path | UrlPart::File | UrlPart::Query);
+    if decoded && decodable {
+        return format!("url.decoded_part(rule_engine::rule::UrlPart::{part:?})");
+    }
+    match part {
+        UrlPart::Host => "url.host.as_str()",
+        UrlPart::Path => "url.path.as_str()",
+        UrlPart::File => "url.file.as_str()",
+        UrlPart::Query => "url.query.as_str()",
+        UrlPart::Domain => "url.registrable_domain()",
+        UrlPart::Full => "url.raw.as_str()",
+        UrlPart::Tld => "url.tld()",
+        UrlPart::Subdomain => "url.subdomain()",
+    }
+    .to_string()
+}
+
+/// Mirrors `Matcher::is_match_in` for one condition, ignoring negation.
+fn operator_expr(cond: &Condition, v: &str, patterns: &mut Patterns) -> String {
+    let value = literal(&cond.value);
+    match cond.operator {
+        Operator::Equals => format!("{v} == {value}"),
+        Operator::Contains => format!("{v}.contains({value})"),
+        Operator::StartsWith => format!("{v}.starts_with({value})"),
+        Operator::EndsWith => format!("{v}.ends_with({value})"),
+        Operator::Glob => {
+            format!("{}.is_match({v})", patterns.static_name("GlobPattern", &cond.value))
+        }
+        Operator::InList => {
+            let members: Vec<String> = cond.list_values().into_iter().map(literal).collect();
+            format!("matches!({v}, {})", members.join(" | "))
+        }
+        Operator::ContainsAtLeast => match cond.min_count {
+            0 => "true".to_string(),
+            1 => format!("{v}.contains({value})"),
+            n => format!("{v}.matches({value}).nth({}).is_some()", n - 1),
+        },
+        Operator::HostSuffix => format!("rule_engine::url::matches_host_suffix({v}, {value})"),
+        Operator::ContainsAny => {
+            let checks: Vec<String> =
+                cond.values.iter().map(|s| format!("{v}.contains({})", literal(s))).collect();
+            if checks.is_empty() { "false".to_string() } else { format!("({})", checks.join(" || ")) }
+        }
+        Operator::Matches => match cond.prefix.len() + cond.suffix.len() {
+            // Both bounds are empty.
+            0 => "true".to_string(),
+            min_len => format!(
+                "({v}.len() >= {min_len} && {v}.starts_with({}) && {v}.ends_with({}))",
+                literal(&cond.prefix),
+                literal(&cond.suffix)
+            ),
+        },
+        Operator::IsEmpty => format!("{v}.is_empty()"),
+        Operator::ExtensionIn => {
+            let exts: Vec<String> = cond
+                .list_values()
+                .into_iter()
+                .map(|ext| literal(ext.strip_prefix('.').unwrap_or(ext)))
+                .collect();
+            format!(
+                "rule_engine::url::file_extension({v})\
+                 .is_some_and(|ext| [{}].iter().any(|e| e.eq_ignore_ascii_case(ext)))",
+                exts.join(", ")
+            )
+        }
+        Operator::SegmentEquals => {
+            let segment = match cond.segment {
+                0 => "next()".to_string(),
+                n => format!("nth({n})"),
+            };
+            format!("{v}.split('/').filter(|s| !s.is_empty()).{segment} == Some({value})")
+        }
+        Operator::HostPattern => {
+            format!("{}.is_match({v})", patterns.static_name("HostPattern", &cond.value))
+        }
+        Operator::EqualsPart => match UrlPart::from_name(&cond.value) {
+            Some(other) => format!("{v} == {}", part_expr(other, cond.decoded)),
+            None => "false".to_string(),
+        },
+        Operator::ParamAbsent if cond.part == UrlPart::Query && cond.param.is_none() => {
+            format!("url.param_pairs({}).all(|(k, _)| k != {value})", cond.decoded)
+        }
+        Operator::ParamAbsent => {
+            format!("rule_engine::url::split_query_params({v}).all(|(k, _)| k != {value})")
+        }
+        Operator::HasSegment => {
+            format!("{v}.split('/').any(|s| !s.is_empty() && s == {value})")
+        }
+        Operator::NumInRange => format!(
+            "rule_engine::url::first_integer({v}).is_some_and(|n| ({}..={}).contains(&n))",
+            cond.min, cond.max
+        ),
+    }
+}
+
+/// Renders `rules`, already in evaluation order, as a Graphviz DOT digraph.
+///
+/// Each rule is a box labelled with its name, priority, result and
+/// conditions. Consecutive rules of equal priority share a cluster, and
+/// dashed edges chain the rules in evaluation order.
+pub(crate) fn dot<'r>(rules: impl IntoIterator<Item = &'r Rule>) -> String {
+    let mut out = String::from("digraph rules {\n    node [shape=box];\n");
+    let mut cluster: Option<i32> = None;
+    let mut count = 0;
+    for (i, rule) in rules.into_iter().enumerate() {
+        if cluster != Some(rule.priority) {
+            if cluster.is_some() {
+                out.push_str("    }\n");
+            }
+            let _ = writeln!(
+                out,
+                "    subgraph cluster_{i} {{\n        label=\"priority {}\";",
+                rule.priority
+            );
+            cluster = Some(rule.priority);
+        }
+        let mut label = format!("{}\npriority {} -> {}", rule.name, rule.priority, rule.result);
+        for cond in &rule.conditions {
+            let not = if cond.negated { "not " } else { "" };
+            let _ = write!(label, "\n{not}{:?} {:?} ", cond.part, cond.operator);
+            let _ = if cond.values.is_empty() {
+                write!(label, "{:?}", cond.value)
+            } else {
+                write!(label, "{:?}", cond.values)
+            };
+        }
+        let _ = writeln!(out, "        r{i} [label=\"{}\"];", dot_escape(&label));
+        count = i + 1;
+    }
+    if cluster.is_some() {
+        out.push_str("    }\n");
+    }
+    for i in 1..count {
+        let _ = writeln!(out, "    r{} -> r{i} [style=dashed];", i - 1);
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Escapes text for a double-quoted DOT string, keeping line breaks.
+fn dot_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Returns a Rust string literal for `s`; `str`'s `Debug` output escapes
+/// exactly what a literal needs.
+fn literal(s: &str) -> String {
+    format!("{s:?}")
+}
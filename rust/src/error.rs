@@ -0,0 +1,129 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Unified error type for the crate's fallible APIs.
+#[derive(Debug)]
+pub enum RuleEngineError {
+    /// Reading a rule or URL file failed.
+    Io(io::Error),
+    /// Rule JSON could not be deserialized.
+    Json(serde_json::Error),
+    /// A URL could not be parsed.
+    InvalidUrl(String),
+    /// Input was well-formed but semantically invalid (e.g. duplicate rule names).
+    Validation(String),
+    /// A compiled engine snapshot could not be encoded or decoded.
+    Snapshot(bincode::Error),
+}
+
+/// Result alias using [`RuleEngineError`].
+pub type Result<T> = std::result::Result<T, RuleEngineError>;
+
+impl fmt::Display for RuleEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleEngineError::Io(e) => write!(f, "I/O error: {}", e),
+            RuleEngineError::Json(e) => write!(f, "Invalid rule JSON: {}", e),
+            RuleEngineError::InvalidUrl(msg) => write!(f, "Invalid URL: {}", msg),
+            RuleEngineError::Validation(msg) => write!(f, "Invalid rules: {}", msg),
+            RuleEngineError::Snapshot(e) => write!(f, "Invalid engine snapshot: {}", e),
+        }
+    }
+}
+
+impl Error for RuleEngineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RuleEngineError::Io(e) => Some(e),
+            RuleEngineError::Json(e) => Some(e),
+            RuleEngineError::Snapshot(e) => Some(e),
+            RuleEngineError::InvalidUrl(_) | RuleEngineError::Validation(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for RuleEngineError {
+    fn from(e: io::Error) -> Self {
+        RuleEngineError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RuleEngineError {
+    fn from(e: serde_json::Error) -> Self {
+        RuleEngineError::Json(e)
+    }
+}
+
+impl From<bincode::Error> for RuleEngineError {
+    fn from(e: bincode::Error) -> Self {
+        match *e {
+            bincode::ErrorKind::Io(io) => RuleEngineError::Io(io),
+            other => RuleEngineError::Snapshot(Box::new(other)),
+        }
+    }
+}
+
+/// Lets callers that still work in `io::Result` use `?` on crate errors.
+impl From<RuleEngineError> for io::Error {
+    fn from(e: RuleEngineError) -> Self {
+        match e {
+            RuleEngineError::Io(e) => e,
+            other => io::Error::new(io::ErrorKind::InvalidData, other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_io_error() {
+        let err: RuleEngineError = io::Error::new(io::ErrorKind::NotFound, "rules.json").into();
+        assert!(matches!(err, RuleEngineError::Io(_)));
+        assert_eq!("I/O error: rules.json", err.to_string());
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn from_json_error() {
+        let json_err = serde_json::from_str::<Vec<u32>>("[").unwrap_err();
+        let err: RuleEngineError = json_err.into();
+        assert!(matches!(err, RuleEngineError::Json(_)));
+        assert!(err.to_string().starts_with("Invalid rule JSON: "));
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn url_and_validation_display() {
+        let url = RuleEngineError::InvalidUrl("URL must not be blank".to_string());
+        assert_eq!("Invalid URL: URL must not be blank", url.to_string());
+        assert!(url.source().is_none());
+
+        let validation = RuleEngineError::Validation("duplicate name 'a'".to_string());
+        assert_eq!("Invalid rules: duplicate name 'a'", validation.to_string());
+    }
+
+    #[test]
+    fn from_bincode_error() {
+        let decode_err = bincode::deserialize::<String>(&[0xFF]).unwrap_err();
+        let err: RuleEngineError = decode_err.into();
+        assert!(matches!(err, RuleEngineError::Io(_) | RuleEngineError::Snapshot(_)));
+
+        let bad_utf8 = bincode::serialize(&vec![0xFFu8]).unwrap();
+        let err: RuleEngineError = bincode::deserialize::<String>(&bad_utf8).unwrap_err().into();
+        assert!(matches!(err, RuleEngineError::Snapshot(_)));
+        assert!(err.to_string().starts_with("Invalid engine snapshot: "));
+    }
+
+    #[test]
+    fn into_io_error() {
+        let io_err: io::Error = RuleEngineError::Io(io::Error::other("disk")).into();
+        assert_eq!("disk", io_err.to_string());
+
+        let io_err: io::Error = RuleEngineError::Validation("bad".to_string()).into();
+        assert_eq!(io::ErrorKind::InvalidData, io_err.kind());
+        assert_eq!("Invalid rules: bad", io_err.to_string());
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+/// A single element of a compiled glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum GlobToken {
+    /// A run of literal characters.
+    Literal(Box<str>),
+    /// `?` — exactly one character.
+    AnyChar,
+    /// `*` — any run of characters, including none.
+    AnyRun,
+}
+
+/// A compiled glob pattern supporting `*` and `?` wildcards.
+///
+/// `*` matches any run of characters, including `/`, so `/api/*` matches
+/// `/api/v2/users`. `?` matches exactly one character. There is no escape
+/// syntax; every other character matches itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GlobPattern {
+    tokens: Vec<GlobToken>,
+}
+
+impl GlobPattern {
+    /// Compiles a glob pattern. Consecutive `*` wildcards are collapsed.
+    pub fn new(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        for c in pattern.chars() {
+            match c {
+                '*' | '?' => {
+                    if !literal.is_empty() {
+                        tokens.push(GlobToken::Literal(std::mem::take(&mut literal).into()));
+                    }
+                    if c == '?' {
+                        tokens.push(GlobToken::AnyChar);
+                    } else if tokens.last() != Some(&GlobToken::AnyRun) {
+                        tokens.push(GlobToken::AnyRun);
+                    }
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(GlobToken::Literal(literal.into()));
+        }
+        Self { tokens }
+    }
+
+    /// Returns `true` if the whole text matches the pattern.
+    ///
+    /// Uses greedy matching with single-point backtracking to the most
+    /// recent `*`, which is linear for typical URL patterns.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.match_starts(text, &mut |_, _| {})
+    }
+
+    /// Matches the whole text and returns the text each wildcard (`*` or
+    /// `?`) consumed, in pattern order, or `None` if the text does not match.
+    ///
+    /// A `*` takes as little as the rest of the pattern allows, so in
+    /// `*/*` the first `*` stops at the first `/`.
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Vec<&'t str>> {
+        let mut starts = vec![0; self.tokens.len()];
+        if !self.match_starts(text, &mut |p, t| starts[p] = t) {
+            return None;
+        }
+        let ends = starts.iter().skip(1).copied().chain([text.len()]);
+        let captures = self
+            .tokens
+            .iter()
+            .zip(starts.iter().zip(ends))
+            .filter(|(token, _)| !matches!(token, GlobToken::Literal(_)))
+            .map(|(_, (&start, end))| &text[start..end])
+            .collect();
+        Some(captures)
+    }
+
+    /// Runs the match, calling `record(token, offset)` whenever a token
+    /// starts matching at a text offset. After a successful match the last
+    /// offset recorded for each token is where it matched.
+    fn match_starts(&self, text: &str, record: &mut impl FnMut(usize, usize)) -> bool {
+        let mut t = 0usize; // byte offset into text
+        let mut p = 0usize; // token index
+        let mut star: Option<(usize, usize)> = None; // (token after `*`, text offset)
+
+        loop {
+            if let Some(token) = self.tokens.get(p) {
+                record(p, t);
+                match token {
+                    GlobToken::AnyRun => {
+                        p += 1;
+                        star = Some((p, t));
+                        continue;
+                    }
+                    GlobToken::AnyChar => {
+                        if let Some(c) = text[t..].chars().next() {
+                            t += c.len_utf8();
+                            p += 1;
+                            continue;
+                        }
+                    }
+                    GlobToken::Literal(lit) => {
+                        if text[t..].starts_with(&**lit) {
+                            t += lit.len();
+                            p += 1;
+                            continue;
+                        }
+                    }
+                }
+            } else if t == text.len() {
+                return true;
+            }
+
+            // Mismatch: let the last `*` absorb one more character.
+            match star {
+                Some((star_p, star_t)) if star_t < text.len() => {
+                    let c = text[star_t..].chars().next().unwrap();
+                    let next_t = star_t + c.len_utf8();
+                    star = Some((star_p, next_t));
+                    p = star_p;
+                    t = next_t;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// A host pattern whose `*` labels each match exactly one host label.
+///
+/// Unlike [`GlobPattern`], a `*` never crosses a `.`: `api.*.example.com`
+/// matches `api.staging.example.com` but not `api.a.b.example.com`. Only a
+/// whole label can be a wildcard; other labels match literally.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostPattern {
+    /// Label literals, with `None` for a `*` label.
+    labels: Vec<Option<Box<str>>>,
+}
+
+impl HostPattern {
+    /// Compiles a host pattern by splitting it into `.`-separated labels.
+    pub fn new(pattern: &str) -> Self {
+        let labels = pattern
+            .split('.')
+            .map(|label| (label != "*").then(|| label.into()))
+            .collect();
+        Self { labels }
+    }
+
+    /// Returns `true` if the host has as many labels as the pattern and
+    /// each matches; a `*` label matches any non-empty label.
+    pub fn is_match(&self, host: &str) -> bool {
+        let mut labels = host.split('.');
+        self.labels.iter().all(|pattern| match (pattern, labels.next()) {
+            (None, Some(label)) => !label.is_empty(),
+            (Some(literal), Some(label)) => **literal == *label,
+            (_, None) => false,
+        }) && labels.next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_return_wildcard_text_in_order() {
+        let glob = GlobPattern::new("/user/*/photo-?.*");
+        assert_eq!(
+            Some(vec!["42", "7", "jpg"]),
+            glob.captures("/user/42/photo-7.jpg")
+        );
+        assert_eq!(None, glob.captures("/users/42/photo-7.jpg"));
+        assert_eq!(Some(vec![]), GlobPattern::new("/about").captures("/about"));
+    }
+
+    #[test]
+    fn captures_after_backtracking_report_final_spans() {
+        let glob = GlobPattern::new("*/*.html");
+        assert_eq!(Some(vec!["a", "b/c"]), glob.captures("a/b/c.html"));
+    }
+
+    #[test]
+    fn literal_requires_exact_match() {
+        let glob = GlobPattern::new("/api/users");
+        assert!(glob.is_match("/api/users"));
+        assert!(!glob.is_match("/api/users/1"));
+        assert!(!glob.is_match("/api"));
+    }
+
+    #[test]
+    fn star_matches_segment() {
+        let glob = GlobPattern::new("/api/*/users");
+        assert!(glob.is_match("/api/v2/users"));
+        assert!(glob.is_match("/api//users"));
+        assert!(!glob.is_match("/api/v2/groups"));
+    }
+
+    #[test]
+    fn star_crosses_slashes() {
+        let glob = GlobPattern::new("/api/*/users");
+        assert!(glob.is_match("/api/v2/internal/users"));
+    }
+
+    #[test]
+    fn question_mark_matches_exactly_one_char() {
+        let glob = GlobPattern::new("index.??ml");
+        assert!(glob.is_match("index.html"));
+        assert!(glob.is_match("index.xhml"));
+        assert!(!glob.is_match("index.hml"));
+        assert!(!glob.is_match("index.xhtml"));
+    }
+
+    #[test]
+    fn question_mark_matches_non_ascii_char() {
+        let glob = GlobPattern::new("caf?");
+        assert!(glob.is_match("caf\u{00E9}"));
+    }
+
+    #[test]
+    fn backtracks_past_partial_literal() {
+        let glob = GlobPattern::new("*ab");
+        assert!(glob.is_match("aab"));
+        assert!(glob.is_match("abab"));
+        assert!(!glob.is_match("aba"));
+    }
+
+    #[test]
+    fn lone_star_matches_everything() {
+        let glob = GlobPattern::new("**");
+        assert!(glob.is_match(""));
+        assert!(glob.is_match("/anything/at/all"));
+    }
+
+    #[test]
+    fn empty_pattern_matches_only_empty() {
+        let glob = GlobPattern::new("");
+        assert!(glob.is_match(""));
+        assert!(!glob.is_match("x"));
+    }
+
+    #[test]
+    fn host_pattern_wildcard_spans_one_label() {
+        let pattern = HostPattern::new("api.*.example.com");
+        assert!(pattern.is_match("api.staging.example.com"));
+        assert!(!pattern.is_match("api.a.b.example.com"));
+        assert!(!pattern.is_match("api..example.com"));
+        assert!(!pattern.is_match("api.example.com"));
+        assert!(!pattern.is_match("web.staging.example.com"));
+    }
+
+    #[test]
+    fn host_pattern_supports_several_wildcards() {
+        let pattern = HostPattern::new("*.*.example.com");
+        assert!(pattern.is_match("a.b.example.com"));
+        assert!(!pattern.is_match("b.example.com"));
+        assert!(!pattern.is_match("x.a.b.example.com"));
+    }
+}
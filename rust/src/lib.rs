@@ -1,3 +1,4 @@
+pub mod error;
 pub mod rule;
 pub mod url;
 pub mod engine;
@@ -5,3 +6,9 @@ pub mod batch;
 pub mod trie;
 pub mod aho_corasick;
 pub mod rule_index;
+pub mod glob;
+pub mod shared;
+mod codegen;
+mod parse_cache;
+mod serde_arrays;
+mod shadow;
@@ -1,25 +1,84 @@
 use std::env;
+use std::io;
 use std::path::Path;
 use std::process;
 
-use rule_engine::batch::BatchProcessor;
+use rule_engine::batch::{BatchProcessor, MatchStatus};
 use rule_engine::engine::RuleEngine;
 use rule_engine::rule::RuleLoader;
 
+const USAGE: &str = "Usage: rule-engine [--format text|jsonl|csv] [--all] <rules.json> <urls.txt>";
+
+/// Output format for evaluation results.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// `url -> result` lines.
+    Text,
+    /// One JSON object per line with `url`, `result` and `status`.
+    JsonLines,
+    /// RFC 4180 CSV with a `url,result` header.
+    Csv,
+}
+
+/// Parsed command-line options.
+struct Options {
+    format: Format,
+    /// Print every matching rule per URL instead of the first result.
+    all: bool,
+    rules_path: String,
+    urls_path: String,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut format = Format::Text;
+        let mut all = false;
+        let mut positional = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--format" => {
+                    let value = iter.next().ok_or("--format requires a value")?;
+                    format = match value.as_str() {
+                        "text" => Format::Text,
+                        "jsonl" => Format::JsonLines,
+                        "csv" => Format::Csv,
+                        other => return Err(format!("Unknown format: {}", other)),
+                    };
+                }
+                "--all" => all = true,
+                _ => positional.push(arg.clone()),
+            }
+        }
+        if all && format != Format::Text {
+            return Err("--all only supports the text format".to_string());
+        }
+        match <[String; 2]>::try_from(positional) {
+            Ok([rules_path, urls_path]) => Ok(Self {
+                format,
+                all,
+                rules_path,
+                urls_path,
+            }),
+            Err(_) => Err(USAGE.to_string()),
+        }
+    }
+}
+
 /// CLI entry point for the rule engine.
 ///
-/// Usage: `rule-engine <rules.json> <urls.txt>`
+/// Usage: `rule-engine [--format text|jsonl|csv] [--all] <rules.json> <urls.txt>`
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
-        eprintln!("Usage: rule-engine <rules.json> <urls.txt>");
-        process::exit(1);
-    }
-
-    let rules_path = Path::new(&args[1]);
-    let urls_path = Path::new(&args[2]);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
 
-    let rules = match RuleLoader::load_from_file(rules_path) {
+    let rules = match RuleLoader::load_from_file(Path::new(&options.rules_path)) {
         Ok(r) => r,
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -30,7 +89,25 @@ fn main() {
     let engine = RuleEngine::new(rules);
     let processor = BatchProcessor::new(&engine);
 
-    let results = match processor.process_file(urls_path) {
+    if options.all {
+        let lines = match std::fs::read_to_string(&options.urls_path) {
+            Ok(content) => content.lines().map(str::to_string).collect::<Vec<_>>(),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        for matches in processor.process_lines_all(&lines) {
+            if matches.status == MatchStatus::InvalidUrl {
+                println!("{} -> INVALID_URL", matches.url);
+            } else {
+                println!("{} -> [{}]", matches.url, matches.rules.join(", "));
+            }
+        }
+        return;
+    }
+
+    let results = match processor.process_file(Path::new(&options.urls_path)) {
         Ok(r) => r,
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -38,7 +115,22 @@ fn main() {
         }
     };
 
-    for result in &results {
-        println!("{} -> {}", result.url, result.result);
+    match options.format {
+        Format::Text => {
+            for result in &results {
+                println!("{} -> {}", result.url, result.result);
+            }
+        }
+        Format::JsonLines => {
+            for result in &results {
+                println!("{}", result.to_json());
+            }
+        }
+        Format::Csv => {
+            if let Err(e) = processor.write_csv(&results, &mut io::stdout().lock()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
     }
 }
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex};
+
+use crate::batch::ParseCacheStats;
+use crate::url::{ParsedUrl, UrlParseError};
+
+/// Outcome of parsing one raw line, shared between cache and callers.
+pub(crate) type Parsed = std::result::Result<Arc<ParsedUrl>, UrlParseError>;
+
+/// Upper bound on the number of independently locked shards.
+const MAX_SHARDS: usize = 16;
+
+/// Smallest shard worth splitting off; smaller caches stay exact LRUs
+/// rather than risking a few hot keys thrashing a tiny shard.
+const MIN_SHARD_CAPACITY: usize = 64;
+
+const NIL: usize = usize::MAX;
+
+/// A thread-safe, bounded LRU cache from raw URL lines to parse results.
+///
+/// Keys are spread over up to 16 `Mutex`-guarded shards of at least 64
+/// entries each, so parallel batches rarely contend. Each shard evicts its
+/// own least recently used entry, so recency is approximate across shards.
+/// Parsing happens outside the lock: two threads missing on the same line
+/// at once may both parse it.
+pub(crate) struct ParseCache {
+    shards: Box<[Mutex<LruShard>]>,
+    hasher: RandomState,
+}
+
+impl ParseCache {
+    /// Creates a cache holding at most `capacity` entries.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub(crate) fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "parse cache capacity must be positive");
+        let shard_count = (capacity / MIN_SHARD_CAPACITY).clamp(1, MAX_SHARDS);
+        let per_shard = capacity.div_ceil(shard_count);
+        Self {
+            shards: (0..shard_count).map(|_| Mutex::new(LruShard::new(per_shard))).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Returns the cached result for `line`, or parses it with `parse` and
+    /// caches the result.
+    pub(crate) fn get_or_parse(
+        &self,
+        line: &str,
+        parse: impl FnOnce(&str) -> std::result::Result<ParsedUrl, UrlParseError>,
+    ) -> Parsed {
+        let shard = &self.shards[self.hasher.hash_one(line) as usize % self.shards.len()];
+        if let Some(parsed) = lock(shard).get(line) {
+            return parsed;
+        }
+        let parsed = parse(line).map(Arc::new);
+        lock(shard).insert(line, parsed.clone());
+        parsed
+    }
+
+    pub(crate) fn stats(&self) -> ParseCacheStats {
+        self.shards.iter().fold(ParseCacheStats::default(), |total, shard| {
+            let shard = lock(shard);
+            ParseCacheStats {
+                hits: total.hits + shard.hits,
+                misses: total.misses + shard.misses,
+                len: total.len + shard.map.len(),
+            }
+        })
+    }
+}
+
+/// Locks a shard, ignoring poisoning: entries are only ever replaced whole.
+fn lock(shard: &Mutex<LruShard>) -> std::sync::MutexGuard<'_, LruShard> {
+    shard.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// One LRU shard: a map into a slab of entries threaded on a doubly
+/// linked recency list, most recent at `head`. Lookups are counted here,
+/// under the lock, rather than in shared atomics.
+struct LruShard {
+    map: HashMap<Box<str>, usize>,
+    entries: Vec<Entry>,
+    head: usize,
+    tail: usize,
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+}
+
+struct Entry {
+    key: Box<str>,
+    value: Parsed,
+    prev: usize,
+    next: usize,
+}
+
+impl LruShard {
+    fn new(capacity: usize) -> Self {
+        Self {
+            map: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            head: NIL,
+            tail: NIL,
+            capacity,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<Parsed> {
+        let Some(&i) = self.map.get(key) else {
+            self.misses += 1;
+            return None;
+        };
+        self.hits += 1;
+        self.unlink(i);
+        self.push_front(i);
+        Some(self.entries[i].value.clone())
+    }
+
+    fn insert(&mut self, key: &str, value: Parsed) {
+        if let Some(&i) = self.map.get(key) {
+            self.entries[i].value = value;
+            self.unlink(i);
+            self.push_front(i);
+            return;
+        }
+        let i = if self.entries.len() < self.capacity {
+            self.entries.push(Entry {
+                key: key.into(),
+                value,
+                prev: NIL,
+                next: NIL,
+            });
+            self.entries.len() - 1
+        } else {
+            // Reuse the least recently used slot.
+            let i = self.tail;
+            self.unlink(i);
+            let evicted = std::mem::replace(&mut self.entries[i].key, key.into());
+            self.map.remove(&evicted);
+            self.entries[i].value = value;
+            i
+        };
+        self.map.insert(key.into(), i);
+        self.push_front(i);
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let (prev, next) = (self.entries[i].prev, self.entries[i].next);
+        match prev {
+            NIL => self.head = next,
+            p => self.entries[p].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            n => self.entries[n].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, i: usize) {
+        self.entries[i].prev = NIL;
+        self.entries[i].next = self.head;
+        match self.head {
+            NIL => self.tail = i,
+            h => self.entries[h].prev = i,
+        }
+        self.head = i;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::url::UrlParser;
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        assert_eq!(1, ParseCache::new(100).shards.len());
+        assert_eq!(16, ParseCache::new(10_000).shards.len());
+        let mut shard = LruShard::new(2);
+        let parsed = |url: &str| UrlParser::parse_detailed(url).map(Arc::new);
+
+        shard.insert("https://a.com/", parsed("https://a.com/"));
+        shard.insert("https://b.com/", parsed("https://b.com/"));
+        assert!(shard.get("https://a.com/").is_some());
+        shard.insert("https://c.com/", parsed("https://c.com/"));
+
+        assert!(shard.get("https://b.com/").is_none());
+        assert_eq!("a.com", shard.get("https://a.com/").unwrap().unwrap().host);
+        assert!(shard.get("https://c.com/").is_some());
+        assert_eq!(2, shard.map.len());
+    }
+
+    #[test]
+    fn errors_are_cached_too() {
+        let cache = ParseCache::new(8);
+        assert_eq!(Err(UrlParseError::Blank), cache.get_or_parse(" ", UrlParser::parse_detailed));
+        assert!(cache.get_or_parse(" ", |_| unreachable!()).is_err());
+        let stats = cache.stats();
+        assert_eq!((1, 1, 1), (stats.hits, stats.misses, stats.len));
+    }
+}
@@ -1,55 +1,213 @@
-use serde::Deserialize;
+use flate2::read::GzDecoder;
+use serde::de::{Deserializer as _, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::fs;
 use std::io::{self, Read};
 use std::path::Path;
 
+use crate::error::{Result, RuleEngineError};
+use crate::url::HostCase;
+
 /// String-matching operators supported by rule conditions.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Operator {
     Equals,
     Contains,
     StartsWith,
     EndsWith,
+    /// Whole-value glob match: `*` matches any run (including `/`), `?` one char.
+    Glob,
+    /// Exact match against any entry of `values`, or of a comma-separated
+    /// `value` when `values` is empty. Checked with one hash lookup however
+    /// many entries there are.
+    InList,
+    /// At least `min_count` non-overlapping occurrences of the value.
+    ContainsAtLeast,
+    /// Suffix match on dot-separated label boundaries: `example.com`
+    /// matches `example.com` and `a.example.com` but not `notexample.com`.
+    /// A leading `.` in the value is ignored.
+    HostSuffix,
+    /// Substring match against any entry of `values`. Counts as a single
+    /// satisfied condition however many entries occur.
+    ContainsAny,
+    /// Starts with `prefix` and ends with `suffix`, without the two
+    /// overlapping. Pre-filtered on the prefix, then checked directly.
+    Matches,
+    /// The part is the empty string; the value is ignored. Negate for
+    /// "is present". Indexed as an `Equals` on `""`.
+    IsEmpty,
+    /// The extension (text after the last `.`) is one of a comma-separated
+    /// list of bare extensions, compared ASCII case-insensitively. Meant for
+    /// the `File` part; dotfiles and names without a `.` have no extension.
+    ExtensionIn,
+    /// The `segment`-th non-empty `/`-separated segment (0-based) equals
+    /// the value. Out-of-range segments never match. Meant for the `Path` part.
+    SegmentEquals,
+    /// Label-by-label host match where a `*` label matches exactly one
+    /// label, e.g. `api.*.example.com`. Unlike `Glob`, `*` never crosses `.`.
+    HostPattern,
+    /// The part equals another part of the same URL, named by the value
+    /// (`host`, `path`, `file`, `query`, `domain`, `full`, `tld` or
+    /// `subdomain`), e.g. a `Host` condition with value `query` for
+    /// anti-SSRF checks.
+    EqualsPart,
+    /// No query parameter has the value as its key, e.g. `token`. Keys are
+    /// compared exactly after splitting as in
+    /// [`split_query_params`](crate::url::split_query_params), so an empty
+    /// query matches. Meant for the `Query` part; negate for "present".
+    ParamAbsent,
+    /// Some non-empty `/`-separated segment equals the value exactly, so
+    /// `admin` matches `/api/admin/users` but not `/administrator`. Empty
+    /// segments from leading, trailing or doubled slashes are skipped.
+    /// Meant for the `Path` part.
+    HasSegment,
+    /// The first run of ASCII digits in the part, read as an integer, lies
+    /// within `[min, max]`, e.g. `3` in `/api/v3/users`. Parts without
+    /// digits, or whose first number overflows `u64`, never match. Combine
+    /// with `param` to read a named query parameter instead.
+    NumInRange,
+}
+
+impl Operator {
+    /// Returns `true` if conditions using this operator can be served by the
+    /// `RuleIndex`. Other operators are evaluated directly at match time.
+    pub fn is_indexable(self) -> bool {
+        !matches!(
+            self,
+            Operator::Glob
+                | Operator::ContainsAtLeast
+                | Operator::ExtensionIn
+                | Operator::SegmentEquals
+                | Operator::HostPattern
+                | Operator::EqualsPart
+                | Operator::ParamAbsent
+                | Operator::HasSegment
+                | Operator::NumInRange
+        )
+    }
 }
 
 /// Represents the decomposed parts of a URL that conditions can target.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum UrlPart {
     Host,
     Path,
     File,
     Query,
+    /// The registrable domain (eTLD+1) derived from the host.
+    Domain,
+    /// The whole trimmed input URL. Only populated by
+    /// `UrlParser::parse_keep_raw`; empty for other parsed URLs.
+    Full,
+    /// The public suffix derived from the host, e.g. `co.uk` for
+    /// `shop.example.co.uk`; empty for IP hosts.
+    Tld,
+    /// The labels of the host left of the registrable domain, e.g.
+    /// `api.shop` for `api.shop.example.com`; empty for apex hosts, public
+    /// suffixes and IP hosts.
+    Subdomain,
 }
 
 /// Number of URL parts (used for flat array indexing).
-pub const URL_PART_COUNT: usize = 4;
+pub const URL_PART_COUNT: usize = 8;
 
 impl UrlPart {
-    /// Returns the ordinal index of this URL part (0-3).
+    /// Returns the ordinal index of this URL part (0-7).
     pub fn ordinal(self) -> usize {
         self as usize
     }
 
+    /// Looks up a part by its JSON name, e.g. `"host"`.
+    pub fn from_name(name: &str) -> Option<UrlPart> {
+        match name {
+            "host" => Some(UrlPart::Host),
+            "path" => Some(UrlPart::Path),
+            "file" => Some(UrlPart::File),
+            "query" => Some(UrlPart::Query),
+            "domain" => Some(UrlPart::Domain),
+            "full" => Some(UrlPart::Full),
+            "tld" => Some(UrlPart::Tld),
+            "subdomain" => Some(UrlPart::Subdomain),
+            _ => None,
+        }
+    }
+
     /// All URL part variants in ordinal order.
     pub const ALL: [UrlPart; URL_PART_COUNT] = [
         UrlPart::Host,
         UrlPart::Path,
         UrlPart::File,
         UrlPart::Query,
+        UrlPart::Domain,
+        UrlPart::Full,
+        UrlPart::Tld,
+        UrlPart::Subdomain,
     ];
 }
 
 /// A single condition within a rule, targeting one URL part with one operator.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Condition {
     pub part: UrlPart,
     pub operator: Operator,
+    /// Operand for single-valued operators; may be omitted for `ContainsAny`.
+    #[serde(default)]
     pub value: String,
+    /// Operands for `ContainsAny`, or entries for `InList` and
+    /// `ExtensionIn`; ignored otherwise.
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// Leading bound for `Matches`; ignored otherwise.
+    #[serde(default)]
+    pub prefix: String,
+    /// Trailing bound for `Matches`; ignored otherwise.
+    #[serde(default)]
+    pub suffix: String,
     #[serde(default)]
     pub negated: bool,
+    /// Minimum occurrence count for `ContainsAtLeast`; ignored otherwise.
+    #[serde(default = "default_min_count")]
+    pub min_count: u32,
+    /// Segment index for `SegmentEquals`; ignored otherwise.
+    #[serde(default)]
+    pub segment: usize,
+    /// Inclusive lower bound for `NumInRange`; ignored otherwise.
+    #[serde(default)]
+    pub min: u64,
+    /// Inclusive upper bound for `NumInRange`; ignored otherwise.
+    #[serde(default = "default_max")]
+    pub max: u64,
+    /// Compare against the percent-decoded part instead of the raw one.
+    /// Only takes effect for URLs parsed with
+    /// [`parse_with_decoded`](crate::url::UrlParser::parse_with_decoded).
+    #[serde(default)]
+    pub decoded: bool,
+    /// Query parameter whose value the operator is applied to, instead of
+    /// the whole query. The condition holds if any pair with this key has a
+    /// matching value, so a missing parameter never matches; negated, it
+    /// holds only if no value for the key matches. Requires the `Query`
+    /// part; such conditions are always checked directly.
+    #[serde(default)]
+    pub param: Option<String>,
+    /// Compare a `Host` `Equals` against the host with one leading `www.`
+    /// removed, so `example.com` also matches `www.example.com`. Ignored
+    /// for other parts and operators.
+    #[serde(default)]
+    pub strip_www: bool,
+}
+
+fn default_min_count() -> u32 {
+    1
+}
+
+fn default_max() -> u64 {
+    u64::MAX
 }
 
 impl Condition {
@@ -59,20 +217,202 @@ impl Condition {
             part,
             operator,
             value: value.into(),
+            values: Vec::new(),
+            prefix: String::new(),
+            suffix: String::new(),
             negated,
+            min_count: default_min_count(),
+            segment: 0,
+            min: 0,
+            max: default_max(),
+            decoded: false,
+            param: None,
+            strip_www: false,
         }
     }
+
+    /// Sets the operands used by `ContainsAny`, or the entries used by
+    /// `InList` and `ExtensionIn`.
+    pub fn with_values<S: Into<String>>(mut self, values: impl IntoIterator<Item = S>) -> Self {
+        self.values = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the minimum occurrence count used by `ContainsAtLeast`.
+    pub fn with_min_count(mut self, min_count: u32) -> Self {
+        self.min_count = min_count;
+        self
+    }
+
+    /// Sets the bounds used by `Matches`.
+    pub fn with_bounds(mut self, prefix: impl Into<String>, suffix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self.suffix = suffix.into();
+        self
+    }
+
+    /// Sets the inclusive bounds used by `NumInRange`.
+    pub fn with_range(mut self, min: u64, max: u64) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    /// Sets the segment index for `SegmentEquals`.
+    pub fn with_segment(mut self, segment: usize) -> Self {
+        self.segment = segment;
+        self
+    }
+
+    /// Sets whether the condition matches the percent-decoded part.
+    pub fn with_decoded(mut self, decoded: bool) -> Self {
+        self.decoded = decoded;
+        self
+    }
+
+    /// Applies the operator to the value of the named query parameter.
+    pub fn with_param(mut self, param: impl Into<String>) -> Self {
+        self.param = Some(param.into());
+        self
+    }
+
+    /// Sets whether a `Host` `Equals` ignores a leading `www.` on the host.
+    pub fn with_strip_www(mut self, strip_www: bool) -> Self {
+        self.strip_www = strip_www;
+        self
+    }
+
+    /// Returns the entries of an `InList` or `ExtensionIn` condition with
+    /// duplicates removed (first occurrence kept): `values` as given, or
+    /// else `value` split on `,` and trimmed.
+    pub fn list_values(&self) -> Vec<&str> {
+        let entries: Box<dyn Iterator<Item = &str>> = if self.values.is_empty() {
+            Box::new(self.value.split(',').map(str::trim))
+        } else {
+            Box::new(self.values.iter().map(String::as_str))
+        };
+        let mut seen = HashSet::new();
+        entries.filter(|v| seen.insert(*v)).collect()
+    }
+
+    /// Returns `true` if this condition is served by the `RuleIndex`
+    /// (non-negated with an indexable operator, on a whole part).
+    pub fn is_indexed(&self) -> bool {
+        !self.negated && self.operator.is_indexable() && self.param.is_none()
+    }
+
+    /// Returns `true` if this is a negated `Contains` on a whole part, which
+    /// the `RuleIndex` serves by disqualifying the rule when the value does
+    /// occur.
+    pub fn is_negated_indexed(&self) -> bool {
+        self.negated && self.operator == Operator::Contains && self.param.is_none()
+    }
+
+    /// Returns `true` if this is a negated condition on a whole part whose
+    /// operator the `RuleIndex` can serve by disqualification: any indexable
+    /// operator except `Matches`, which the index only pre-filters.
+    pub fn is_negated_indexable(&self) -> bool {
+        self.negated
+            && self.operator.is_indexable()
+            && self.operator != Operator::Matches
+            && self.param.is_none()
+    }
+
+    /// Returns `true` if the engine must check this condition itself at
+    /// match time: the index does not serve it, or only pre-filters it.
+    /// An index built with negations may still serve such negated
+    /// conditions of unindexed rules; see
+    /// [`RuleIndex::serves_negated`](crate::rule_index::RuleIndex::serves_negated).
+    pub fn needs_direct_check(&self) -> bool {
+        !(self.is_indexed() || self.is_negated_indexed()) || self.operator == Operator::Matches
+    }
+
+    /// Returns the condition as the index and matchers see it.
+    ///
+    /// Operands of conditions on the host or a part derived from it are
+    /// case-folded the way `host_case` folds parsed hosts, so under the
+    /// default [`HostCase::Unicode`] a value written `.CA` still matches
+    /// `example.ca`, and under [`HostCase::Preserve`] operands are kept as
+    /// written; path and query operands keep their case. A `Host` `Equals`
+    /// with `strip_www` becomes an `InList` of the host forms it accepts:
+    /// the value with `www.` prepended, and the value itself unless it
+    /// already starts with `www.`.
+    pub(crate) fn normalized(&self, host_case: HostCase) -> Cow<'_, Condition> {
+        let host_derived = matches!(
+            self.part,
+            UrlPart::Host | UrlPart::Domain | UrlPart::Tld | UrlPart::Subdomain
+        );
+        let has_upper = |s: &String| match host_case {
+            HostCase::Unicode => s.chars().any(char::is_uppercase),
+            HostCase::Ascii => s.bytes().any(|b| b.is_ascii_uppercase()),
+            HostCase::Preserve => false,
+        };
+        let fold = host_derived
+            && self.operator != Operator::EqualsPart
+            && (has_upper(&self.value)
+                || self.values.iter().any(has_upper)
+                || has_upper(&self.prefix)
+                || has_upper(&self.suffix));
+        let www = self.strip_www && self.part == UrlPart::Host && self.operator == Operator::Equals;
+        if !fold && !www {
+            return Cow::Borrowed(self);
+        }
+        let mut normalized = self.clone();
+        if fold {
+            let lower = |s: &str| match host_case {
+                HostCase::Ascii => s.to_ascii_lowercase(),
+                _ => s.to_lowercase(),
+            };
+            normalized.value = lower(&normalized.value);
+            normalized.values.iter_mut().for_each(|v| *v = lower(v));
+            normalized.prefix = lower(&normalized.prefix);
+            normalized.suffix = lower(&normalized.suffix);
+        }
+        if www {
+            let value = std::mem::take(&mut normalized.value);
+            normalized.operator = Operator::InList;
+            normalized.values = vec![format!("www.{value}")];
+            if !value.starts_with("www.") {
+                normalized.values.push(value);
+            }
+            normalized.strip_www = false;
+        }
+        Cow::Owned(normalized)
+    }
 }
 
-/// A named rule consisting of one or more conditions and a result string.
+/// A named rule consisting of conditions and a result string.
 ///
 /// Rules are compared by priority in descending order (highest first).
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+/// Disabled rules are kept by the engine but never match. A rule with no
+/// conditions matches every URL, which makes a low-priority conditionless
+/// rule a catch-all; `conditions` may be omitted from JSON for such rules.
+/// Unknown JSON fields are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Rule {
+    /// Optional stable identifier for external references. Unlike the
+    /// engine's internal dense IDs it does not change when rules are
+    /// reordered, and must be unique within a rule set.
+    #[serde(default)]
+    pub id: Option<String>,
     pub name: String,
     pub priority: i32,
+    #[serde(default)]
     pub conditions: Vec<Condition>,
     pub result: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Free-form note for rule authors; ignored by matching.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Labels for tooling, e.g. to select rules by team or purpose; ignored
+    /// by matching.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 impl Rule {
@@ -84,14 +424,101 @@ impl Rule {
         result: impl Into<String>,
     ) -> Self {
         Self {
+            id: None,
             name: name.into(),
             priority,
             conditions,
             result: result.into(),
+            enabled: true,
+            description: None,
+            tags: Vec::new(),
         }
     }
 }
 
+impl Rule {
+    /// Returns a builder for a rule with the given name, adding conditions
+    /// one at a time.
+    pub fn builder(name: impl Into<String>) -> RuleBuilder {
+        RuleBuilder {
+            rule: Rule::new(name, 0, Vec::new(), ""),
+        }
+    }
+
+    /// Sets the stable external identifier.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the author's description.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets the tags.
+    pub fn with_tags<S: Into<String>>(mut self, tags: impl IntoIterator<Item = S>) -> Self {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Returns `true` if the rule carries the tag, compared exactly.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Returns `true` if no condition counts towards the rule's index total,
+    /// so the engine considers the rule on every evaluation.
+    pub fn is_unindexed(&self) -> bool {
+        !self.conditions.iter().any(Condition::is_indexed)
+    }
+}
+
+/// Fluent builder for [`Rule`], started by [`Rule::builder`].
+///
+/// Priority defaults to 0 and the result to the empty string; conditions are
+/// kept in the order they are added.
+#[derive(Debug, Clone)]
+pub struct RuleBuilder {
+    rule: Rule,
+}
+
+impl RuleBuilder {
+    /// Sets the priority.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.rule.priority = priority;
+        self
+    }
+
+    /// Adds a condition that must match.
+    pub fn when(self, part: UrlPart, operator: Operator, value: impl Into<String>) -> Self {
+        self.condition(Condition::new(part, operator, value, false))
+    }
+
+    /// Adds a condition that must not match.
+    pub fn when_not(self, part: UrlPart, operator: Operator, value: impl Into<String>) -> Self {
+        self.condition(Condition::new(part, operator, value, true))
+    }
+
+    /// Adds a prebuilt condition, for operators needing more than a value.
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.rule.conditions.push(condition);
+        self
+    }
+
+    /// Sets the result returned when the rule matches.
+    pub fn result(mut self, result: impl Into<String>) -> Self {
+        self.rule.result = result.into();
+        self
+    }
+
+    /// Builds the rule.
+    pub fn build(self) -> Rule {
+        self.rule
+    }
+}
+
 impl Ord for Rule {
     fn cmp(&self, other: &Self) -> Ordering {
         // Descending priority (higher = first)
@@ -105,29 +532,363 @@ impl PartialOrd for Rule {
     }
 }
 
-/// Loads rules from JSON.
+/// First bytes of every gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// File signature written at the start of every binary rule file.
+const RULES_BIN_MAGIC: &[u8; 4] = b"RRUL";
+
+/// Bumped whenever the serialized `Rule` or `Condition` layout changes.
+const RULES_BIN_VERSION: u32 = 6;
+
+fn bin_options() -> impl bincode::Options {
+    bincode::DefaultOptions::new()
+}
+
+fn bin_error(msg: String) -> RuleEngineError {
+    RuleEngineError::Snapshot(Box::new(bincode::ErrorKind::Custom(msg)))
+}
+
+/// Differences between two rule sets, computed by [`RuleLoader::diff`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RuleDiff<'r> {
+    /// Rules only in the new set, in new-set order.
+    pub added: Vec<&'r Rule>,
+    /// Rules only in the old set, in old-set order.
+    pub removed: Vec<&'r Rule>,
+    /// `(old, new)` pairs sharing a name whose behavior differs, in new-set
+    /// order.
+    pub changed: Vec<(&'r Rule, &'r Rule)>,
+}
+
+impl RuleDiff<'_> {
+    /// Returns `true` if the two rule sets behave the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Loads rules from JSON, or from the compact binary format written by
+/// [`save_bin`](RuleLoader::save_bin).
 pub struct RuleLoader;
 
 impl RuleLoader {
-    /// Loads rules from a JSON file.
-    pub fn load_from_file(path: &Path) -> io::Result<Vec<Rule>> {
-        let content = fs::read_to_string(path)?;
+    /// Loads rules from a JSON file, decompressing it first if it is
+    /// gzipped (detected by its leading magic bytes, not its extension).
+    ///
+    /// A truncated or corrupt gzip stream fails with an `Io` error naming
+    /// the file.
+    pub fn load_from_file(path: &Path) -> Result<Vec<Rule>> {
+        let bytes = fs::read(path)?;
+        let content = if bytes.starts_with(GZIP_MAGIC) {
+            let mut content = String::new();
+            GzDecoder::new(bytes.as_slice())
+                .read_to_string(&mut content)
+                .map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("corrupt gzip rule file {}: {}", path.display(), e),
+                    )
+                })?;
+            content
+        } else {
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        };
         Self::load_from_str(&content)
     }
 
+    /// Loads a blocklist file as a single `InList` rule on `part` with the
+    /// given name and priority, that yields `result` when the part is listed.
+    ///
+    /// The file holds one entry per line; blank lines and lines starting
+    /// with `#` are skipped. However long the list, the engine indexes the
+    /// rule once and checks it with a single hash lookup.
+    pub fn load_blocklist(
+        path: &Path,
+        part: UrlPart,
+        name: impl Into<String>,
+        priority: i32,
+        result: impl Into<String>,
+    ) -> Result<Rule> {
+        let content = fs::read_to_string(path)?;
+        let entries = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let cond = Condition::new(part, Operator::InList, "", false).with_values(entries);
+        Ok(Rule::new(name, priority, vec![cond], result))
+    }
+
+    /// Loads rules from several JSON files and concatenates them.
+    ///
+    /// Rules keep file order, then in-file order, so definition-order
+    /// tie-breaking in `RuleEngine` follows the order of `paths`. Returns an
+    /// `Validation` error if the same rule name appears in two different files,
+    /// or if the combined rules fail [`validate`](Self::validate).
+    pub fn load_from_files(paths: &[&Path]) -> Result<Vec<Rule>> {
+        let mut rules = Vec::new();
+        let mut origins: HashMap<String, usize> = HashMap::new();
+        for (file_index, path) in paths.iter().enumerate() {
+            for rule in Self::load_from_file(path)? {
+                let origin = *origins.entry(rule.name.clone()).or_insert(file_index);
+                if origin != file_index {
+                    return Err(RuleEngineError::Validation(format!(
+                        "Duplicate rule name '{}' in {} (already defined in {})",
+                        rule.name,
+                        path.display(),
+                        paths[origin].display()
+                    )));
+                }
+                rules.push(rule);
+            }
+        }
+        Self::validate(&rules)?;
+        Ok(rules)
+    }
+
+    /// Writes rules in a compact, versioned binary format that
+    /// [`load_bin`](Self::load_bin) reads back much faster than JSON.
+    ///
+    /// The format is tied to this crate's rule layout and is meant as a
+    /// load-time cache, not for long-term storage.
+    pub fn save_bin(rules: &[Rule], path: &Path) -> Result<()> {
+        use bincode::Options;
+        let mut bytes = RULES_BIN_MAGIC.to_vec();
+        bytes.extend_from_slice(&RULES_BIN_VERSION.to_le_bytes());
+        bin_options().serialize_into(&mut bytes, rules)?;
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Loads rules written by [`save_bin`](Self::save_bin) and
+    /// [validates](Self::validate) them.
+    ///
+    /// Fails with a `Snapshot` error if the file lacks the magic header, was
+    /// written by another format version, or is truncated or corrupted.
+    /// Decoding is bounded by the file size, so corrupted lengths cannot
+    /// trigger huge allocations.
+    pub fn load_bin(path: &Path) -> Result<Vec<Rule>> {
+        use bincode::Options;
+        let bytes = fs::read(path)?;
+        let header = RULES_BIN_MAGIC.len() + 4;
+        if bytes.len() < header || &bytes[..RULES_BIN_MAGIC.len()] != RULES_BIN_MAGIC {
+            return Err(bin_error("not a binary rule file".to_string()));
+        }
+        let version = u32::from_le_bytes(bytes[RULES_BIN_MAGIC.len()..header].try_into().unwrap());
+        if version != RULES_BIN_VERSION {
+            return Err(bin_error(format!(
+                "unsupported rule file version {} (expected {})",
+                version, RULES_BIN_VERSION
+            )));
+        }
+        let body = &bytes[header..];
+        let rules: Vec<Rule> = bin_options()
+            .with_limit(body.len() as u64)
+            .deserialize(body)
+            .map_err(RuleEngineError::Snapshot)?;
+        Self::validate(&rules)?;
+        Ok(rules)
+    }
+
     /// Loads rules from a reader providing JSON content.
-    pub fn load_from_reader(reader: &mut dyn Read) -> io::Result<Vec<Rule>> {
+    pub fn load_from_reader(reader: &mut dyn Read) -> Result<Vec<Rule>> {
         let mut content = String::new();
         reader.read_to_string(&mut content)?;
         Self::load_from_str(&content)
     }
 
-    /// Loads rules from a JSON string.
-    pub fn load_from_str(json: &str) -> io::Result<Vec<Rule>> {
-        let rules: Vec<Rule> =
-            serde_json::from_str(json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    /// Loads rules from a JSON string and [validates](Self::validate) them.
+    ///
+    /// The error converts into `io::Error` for callers working in `io::Result`.
+    pub fn load_from_str(json: &str) -> Result<Vec<Rule>> {
+        let rules: Vec<Rule> = serde_json::from_str(json)?;
+        Self::validate(&rules)?;
+        Ok(rules)
+    }
+
+    /// Parses rules like [`load_from_str`](Self::load_from_str), replacing
+    /// `${NAME}` references in condition operands (`value`, `values`,
+    /// `prefix` and `suffix`) with entries of `vars`.
+    ///
+    /// Substituted text is not scanned again, and a `$` not followed by `{`
+    /// is kept as written. Returns a `Validation` error for an unknown name
+    /// or an unterminated `${`.
+    pub fn load_from_str_with_vars(
+        json: &str,
+        vars: &HashMap<String, String>,
+    ) -> Result<Vec<Rule>> {
+        let mut rules: Vec<Rule> = serde_json::from_str(json)?;
+        for rule in &mut rules {
+            for cond in &mut rule.conditions {
+                let operands = std::iter::once(&mut cond.value)
+                    .chain(&mut cond.values)
+                    .chain([&mut cond.prefix, &mut cond.suffix]);
+                for operand in operands {
+                    if let Cow::Owned(substituted) = substitute_vars(operand, vars)
+                        .map_err(|e| {
+                            RuleEngineError::Validation(format!("{} in '{}'", e, rule.name))
+                        })?
+                    {
+                        *operand = substituted;
+                    }
+                }
+            }
+        }
+        Self::validate(&rules)?;
         Ok(rules)
     }
+
+    /// Streams rules from a JSON array, handing each to `callback` as soon as
+    /// it is parsed so at most one rule is held in memory at a time.
+    ///
+    /// Returns the number of rules streamed. Rules are not
+    /// [validated](Self::validate) as a set. Wrap unbuffered readers such as
+    /// `File` in a `BufReader`.
+    pub fn load_streaming<R: Read>(reader: R, callback: &mut impl FnMut(Rule)) -> Result<usize> {
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let count = de.deserialize_seq(RuleSink { callback })?;
+        de.end()?;
+        Ok(count)
+    }
+
+    /// Removes rules equal in every field to an earlier one, keeping first
+    /// occurrences in order. Compare lengths to see how many were dropped.
+    ///
+    /// Rules differing only in, say, `description` or `enabled` are kept.
+    /// Duplicates sharing an `id` would otherwise fail
+    /// [`validate`](Self::validate).
+    pub fn dedup_rules(rules: Vec<Rule>) -> Vec<Rule> {
+        let mut seen = HashSet::with_capacity(rules.len());
+        let keep: Vec<bool> = rules.iter().map(|rule| seen.insert(rule)).collect();
+        rules
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(rule, keep)| keep.then_some(rule))
+            .collect()
+    }
+
+    /// Compares two rule sets, e.g. for reviewing a deploy.
+    ///
+    /// Rules are paired by name; when a name repeats, its n-th occurrence in
+    /// `old` pairs with its n-th in `new`. A pair counts as changed if its
+    /// conditions, priority, result or enabled flag differ; ids,
+    /// descriptions and tags are ignored, as is the order of rules.
+    pub fn diff<'r>(old: &'r [Rule], new: &'r [Rule]) -> RuleDiff<'r> {
+        // Positions in `old` of each name's not yet paired occurrences.
+        let mut unpaired: HashMap<&str, VecDeque<usize>> = HashMap::new();
+        for (i, rule) in old.iter().enumerate() {
+            unpaired.entry(&rule.name).or_default().push_back(i);
+        }
+        let mut paired = vec![false; old.len()];
+        let mut diff = RuleDiff::default();
+        for rule in new {
+            let Some(i) = unpaired.get_mut(rule.name.as_str()).and_then(VecDeque::pop_front) else {
+                diff.added.push(rule);
+                continue;
+            };
+            paired[i] = true;
+            let before = &old[i];
+            if before.conditions != rule.conditions
+                || before.priority != rule.priority
+                || before.result != rule.result
+                || before.enabled != rule.enabled
+            {
+                diff.changed.push((before, rule));
+            }
+        }
+        diff.removed = old.iter().zip(paired).filter(|(_, p)| !p).map(|(r, _)| r).collect();
+        diff
+    }
+
+    /// Checks rule-set invariants that JSON parsing cannot express.
+    ///
+    /// Returns a `Validation` error if two rules declare the same `id`, or
+    /// an `EqualsPart` condition names no known part. Names need not be
+    /// unique.
+    pub fn validate(rules: &[Rule]) -> Result<()> {
+        let mut ids: HashMap<&str, &str> = HashMap::new();
+        for rule in rules {
+            if let Some(cond) = rule.conditions.iter().find(|c| {
+                c.operator == Operator::EqualsPart && UrlPart::from_name(&c.value).is_none()
+            }) {
+                return Err(RuleEngineError::Validation(format!(
+                    "Unknown URL part '{}' in equals_part condition of '{}'",
+                    cond.value, rule.name
+                )));
+            }
+            if let Some(cond) = rule
+                .conditions
+                .iter()
+                .find(|c| c.param.is_some() && c.part != UrlPart::Query)
+            {
+                return Err(RuleEngineError::Validation(format!(
+                    "Parameter condition on '{}' targets {:?} instead of the query in '{}'",
+                    cond.param.as_deref().unwrap_or_default(),
+                    cond.part,
+                    rule.name
+                )));
+            }
+            if let Some(id) = rule.id.as_deref()
+                && let Some(first) = ids.insert(id, &rule.name)
+            {
+                return Err(RuleEngineError::Validation(format!(
+                    "Duplicate rule id '{}' on '{}' (already used by '{}')",
+                    id, rule.name, first
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces every `${NAME}` in `value` with `vars[NAME]`, borrowing the
+/// input when it has no references.
+fn substitute_vars<'v>(
+    value: &'v str,
+    vars: &HashMap<String, String>,
+) -> std::result::Result<Cow<'v, str>, String> {
+    if !value.contains("${") {
+        return Ok(Cow::Borrowed(value));
+    }
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unterminated variable reference in '{}'", value))?;
+        let name = &after[..end];
+        let substitute = vars
+            .get(name)
+            .ok_or_else(|| format!("Unknown variable '{}'", name))?;
+        out.push_str(substitute);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Cow::Owned(out))
+}
+
+/// Visits a JSON array of rules, forwarding each element to a callback.
+struct RuleSink<'f, F> {
+    callback: &'f mut F,
+}
+
+impl<'de, F: FnMut(Rule)> Visitor<'de> for RuleSink<'_, F> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of rules")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<usize, A::Error> {
+        let mut count = 0;
+        while let Some(rule) = seq.next_element::<Rule>()? {
+            (self.callback)(rule);
+            count += 1;
+        }
+        Ok(count)
+    }
 }
 
 #[cfg(test)]
@@ -168,6 +929,91 @@ mod tests {
         assert_eq!(Operator::StartsWith, cond.operator);
     }
 
+    #[test]
+    fn builder_matches_new_for_canada_sport() {
+        let built = Rule::builder("Canada Sport")
+            .priority(10)
+            .when(UrlPart::Host, Operator::EndsWith, ".ca")
+            .when(UrlPart::Path, Operator::Contains, "sport")
+            .result("Canada Sport")
+            .build();
+        let expected = Rule::new(
+            "Canada Sport",
+            10,
+            vec![
+                Condition::new(UrlPart::Host, Operator::EndsWith, ".ca", false),
+                Condition::new(UrlPart::Path, Operator::Contains, "sport", false),
+            ],
+            "Canada Sport",
+        );
+        assert_eq!(expected, built);
+
+        let rules = RuleLoader::load_from_str(TEST_RULES_JSON).unwrap();
+        assert_eq!(rules[0], built);
+    }
+
+    #[test]
+    fn builder_when_not_adds_negated_condition() {
+        let built = Rule::builder("Not Admin")
+            .when_not(UrlPart::Path, Operator::StartsWith, "/admin")
+            .condition(Condition::new(UrlPart::Path, Operator::SegmentEquals, "x", false).with_segment(1))
+            .build();
+
+        assert_eq!(0, built.priority);
+        assert_eq!("", built.result);
+        assert!(built.conditions[0].negated);
+        assert_eq!(1, built.conditions[1].segment);
+    }
+
+    #[test]
+    fn load_from_file_decompresses_gzip() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(TEST_RULES_JSON.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("rule-engine-gzip-{}.json.gz", std::process::id()));
+        let truncated = dir.join(format!("rule-engine-gzip-{}-cut.gz", std::process::id()));
+        fs::write(&path, &gzipped).unwrap();
+        fs::write(&truncated, &gzipped[..gzipped.len() / 2]).unwrap();
+
+        let loaded = RuleLoader::load_from_file(&path);
+        let err = RuleLoader::load_from_file(&truncated).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&truncated).unwrap();
+
+        assert_eq!(RuleLoader::load_from_str(TEST_RULES_JSON).unwrap(), loaded.unwrap());
+        assert!(matches!(err, RuleEngineError::Io(_)));
+        assert!(err.to_string().contains("corrupt gzip rule file"), "{err}");
+    }
+
+    fn data_file(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name)
+    }
+
+    #[test]
+    fn load_from_files_concatenates_in_file_order() {
+        let first = data_file("test-rules.json");
+        let second = data_file("integration-rules.json");
+        let single = RuleLoader::load_from_file(&second).unwrap();
+
+        let rules = RuleLoader::load_from_files(&[&first, &second]).unwrap();
+        assert_eq!(3 + single.len(), rules.len());
+        assert_eq!("Canada Sport", rules[0].name);
+        assert_eq!(single[0].name, rules[3].name);
+    }
+
+    #[test]
+    fn load_from_files_flags_duplicate_names_across_files() {
+        let file = data_file("test-rules.json");
+        let err = RuleLoader::load_from_files(&[&file, &file]).unwrap_err();
+        assert!(matches!(err, RuleEngineError::Validation(_)));
+        assert!(err.to_string().contains("Canada Sport"));
+    }
+
     #[test]
     fn case_insensitive_enums() {
         let json = r#"[{"name":"test","priority":1,"conditions":[
@@ -177,6 +1023,267 @@ mod tests {
         assert_eq!(UrlPart::Host, rules[0].conditions[0].part);
     }
 
+    #[test]
+    fn parses_glob_operator() {
+        let json = r#"[{"name":"g","priority":1,"conditions":[
+          {"part":"path","operator":"glob","value":"/api/*"}
+        ],"result":"ok"}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        assert_eq!(Operator::Glob, rules[0].conditions[0].operator);
+        assert!(!rules[0].conditions[0].is_indexed());
+    }
+
+    #[test]
+    fn parses_in_list_operator() {
+        let json = r#"[{"name":"l","priority":1,"conditions":[
+          {"part":"host","operator":"in_list","value":"a.com, b.com,a.com"}
+        ],"result":"ok"}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        let cond = &rules[0].conditions[0];
+        assert_eq!(Operator::InList, cond.operator);
+        assert_eq!(vec!["a.com", "b.com"], cond.list_values());
+    }
+
+    #[test]
+    fn list_values_prefer_values_over_value() {
+        let cond = Condition::new(UrlPart::Host, Operator::InList, "x.com", false)
+            .with_values(["a,b.com", "c.com", "a,b.com"]);
+        assert_eq!(vec!["a,b.com", "c.com"], cond.list_values());
+    }
+
+    #[test]
+    fn strip_www_equals_normalizes_to_accepted_host_forms() {
+        let cond = |value: &str| {
+            Condition::new(UrlPart::Host, Operator::Equals, value, false).with_strip_www(true)
+        };
+        let forms = |c: &Condition| c.normalized(HostCase::Unicode).list_values().join(" ");
+        assert_eq!("www.example.com example.com", forms(&cond("Example.com")));
+        assert_eq!("www.www.example.com", forms(&cond("www.example.com")));
+        let path = Condition::new(UrlPart::Path, Operator::Equals, "/A", false).with_strip_www(true);
+        assert!(matches!(path.normalized(HostCase::Unicode), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn diff_sorts_rules_into_added_removed_and_changed() {
+        let ca = |priority| {
+            Rule::new(
+                "ca",
+                priority,
+                vec![Condition::new(UrlPart::Host, Operator::EndsWith, ".ca", false)],
+                "canada",
+            )
+        };
+        let old = vec![ca(1), Rule::new("gone", 1, vec![], "x"), Rule::new("same", 1, vec![], "s")];
+        let new = vec![
+            Rule::new("same", 1, vec![], "s").with_description("only the note changed"),
+            Rule::new("fresh", 1, vec![], "y"),
+            ca(5),
+        ];
+
+        let diff = RuleLoader::diff(&old, &new);
+        assert_eq!(vec![&new[1]], diff.added);
+        assert_eq!(vec![&old[1]], diff.removed);
+        assert_eq!(vec![(&old[0], &new[2])], diff.changed);
+        assert!(!diff.is_empty());
+        assert!(RuleLoader::diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn diff_pairs_repeated_names_in_order() {
+        let old = vec![Rule::new("dup", 1, vec![], "a"), Rule::new("dup", 1, vec![], "b")];
+        let new = vec![Rule::new("dup", 1, vec![], "a")];
+
+        let diff = RuleLoader::diff(&old, &new);
+        assert_eq!(RuleDiff { removed: vec![&old[1]], ..RuleDiff::default() }, diff);
+    }
+
+    #[test]
+    fn dedup_rules_drops_exact_duplicates_only() {
+        let ca = Rule::new(
+            "ca",
+            2,
+            vec![Condition::new(UrlPart::Host, Operator::EndsWith, ".ca", false)],
+            "canada",
+        );
+        let mut described = ca.clone();
+        described.description = Some("same match, different note".to_string());
+        let rules = vec![
+            ca.clone(),
+            Rule::new("all", 1, vec![], "x"),
+            ca.clone(),
+            described.clone(),
+        ];
+
+        let deduped = RuleLoader::dedup_rules(rules);
+        assert_eq!(vec![ca, Rule::new("all", 1, vec![], "x"), described], deduped);
+    }
+
+    #[test]
+    fn substitutes_variables_in_operands() {
+        let json = r#"[{"name":"shop","priority":1,"result":"${KEEP}","conditions":[
+          {"part":"host","operator":"equals","value":"${BASE}.example.com"},
+          {"part":"path","operator":"in_list","values":["/${BASE}","/$1"]}
+        ]}]"#;
+        let vars = HashMap::from([
+            ("BASE".to_string(), "shop".to_string()),
+            ("KEEP".to_string(), "x".to_string()),
+        ]);
+        let rules = RuleLoader::load_from_str_with_vars(json, &vars).unwrap();
+        assert_eq!("shop.example.com", rules[0].conditions[0].value);
+        assert_eq!(vec!["/shop", "/$1"], rules[0].conditions[1].values);
+        assert_eq!("${KEEP}", rules[0].result);
+
+        for (value, message) in [
+            ("${MISSING}.com", "Unknown variable 'MISSING' in 'r'"),
+            ("${BASE", "Unterminated variable reference in '${BASE' in 'r'"),
+        ] {
+            let json = format!(
+                r#"[{{"name":"r","priority":1,"result":"x","conditions":[
+                  {{"part":"host","operator":"equals","value":"{value}"}}]}}]"#
+            );
+            match RuleLoader::load_from_str_with_vars(&json, &vars) {
+                Err(RuleEngineError::Validation(msg)) => assert_eq!(message, msg),
+                other => panic!("expected a validation error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_contains_at_least_operator() {
+        let json = r#"[{"name":"c","priority":1,"conditions":[
+          {"part":"query","operator":"contains_at_least","value":"utm_","min_count":2},
+          {"part":"query","operator":"contains","value":"x"}
+        ],"result":"ok"}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        assert_eq!(Operator::ContainsAtLeast, rules[0].conditions[0].operator);
+        assert_eq!(2, rules[0].conditions[0].min_count);
+        assert_eq!(1, rules[0].conditions[1].min_count);
+    }
+
+    #[test]
+    fn parses_contains_any_operator() {
+        let json = r#"[{"name":"topics","priority":1,"conditions":[
+          {"part":"path","operator":"contains_any","values":["sport","news"]}
+        ],"result":"ok"}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        let cond = &rules[0].conditions[0];
+        assert_eq!(Operator::ContainsAny, cond.operator);
+        assert_eq!(vec!["sport", "news"], cond.values);
+        assert!(cond.is_indexed());
+    }
+
+    #[test]
+    fn parses_optional_rule_id() {
+        let json = r#"[
+          {"id":"r-1","name":"a","priority":1,"result":"x"},
+          {"name":"b","priority":1,"result":"y"}
+        ]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        assert_eq!(Some("r-1"), rules[0].id.as_deref());
+        assert_eq!(None, rules[1].id);
+    }
+
+    #[test]
+    fn validate_allows_shared_names_but_not_ids() {
+        let a = Rule::new("same", 1, vec![], "x").with_id("one");
+        let b = Rule::new("same", 1, vec![], "y").with_id("two");
+        assert!(RuleLoader::validate(&[a.clone(), b]).is_ok());
+
+        let c = Rule::new("other", 1, vec![], "z").with_id("one");
+        let err = RuleLoader::validate(&[a, c]).unwrap_err();
+        assert!(matches!(err, RuleEngineError::Validation(_)));
+        assert!(err.to_string().contains("'one'"));
+    }
+
+    #[test]
+    fn parses_matches_operator() {
+        let json = r#"[{"name":"m","priority":1,"conditions":[
+          {"part":"path","operator":"matches","prefix":"/api","suffix":".json"}
+        ],"result":"ok"}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        let cond = &rules[0].conditions[0];
+        assert_eq!(Operator::Matches, cond.operator);
+        assert_eq!(("/api", ".json"), (cond.prefix.as_str(), cond.suffix.as_str()));
+        assert!(cond.is_indexed());
+        assert!(cond.needs_direct_check());
+    }
+
+    #[test]
+    fn load_streaming_yields_each_rule() {
+        let entries: Vec<String> = (0..10_000)
+            .map(|i| {
+                format!(
+                    r#"{{"name":"r{}","priority":{},"conditions":[
+                      {{"part":"host","operator":"equals","value":"h{}.com"}}
+                    ],"result":"x"}}"#,
+                    i, i, i
+                )
+            })
+            .collect();
+        let json = format!("[{}]", entries.join(","));
+
+        let mut seen = 0;
+        let mut last_name = String::new();
+        let count = RuleLoader::load_streaming(json.as_bytes(), &mut |rule: Rule| {
+            seen += 1;
+            last_name = rule.name;
+        })
+        .unwrap();
+        assert_eq!(10_000, count);
+        assert_eq!(10_000, seen);
+        assert_eq!("r9999", last_name);
+    }
+
+    #[test]
+    fn load_streaming_rejects_non_array() {
+        let err = RuleLoader::load_streaming(&b"{}"[..], &mut |_| {}).unwrap_err();
+        assert!(matches!(err, RuleEngineError::Json(_)));
+    }
+
+    #[test]
+    fn missing_conditions_default_to_empty() {
+        let json = r#"[{"name":"fallback","priority":1,"result":"other"}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        assert!(rules[0].conditions.is_empty());
+    }
+
+    #[test]
+    fn parses_host_suffix_operator() {
+        let json = r#"[{"name":"h","priority":1,"conditions":[
+          {"part":"host","operator":"host_suffix","value":"example.com"}
+        ],"result":"ok"}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        assert_eq!(Operator::HostSuffix, rules[0].conditions[0].operator);
+        assert!(rules[0].conditions[0].is_indexed());
+    }
+
+    #[test]
+    fn enabled_defaults_to_true() {
+        let rules = RuleLoader::load_from_str(TEST_RULES_JSON).unwrap();
+        assert!(rules.iter().all(|r| r.enabled));
+    }
+
+    #[test]
+    fn parses_disabled_rule() {
+        let json = r#"[{"name":"off","priority":1,"conditions":[
+          {"part":"host","operator":"equals","value":"x"}
+        ],"result":"ok","enabled":false}]"#;
+        let rules = RuleLoader::load_from_str(json).unwrap();
+        assert!(!rules[0].enabled);
+    }
+
+    #[test]
+    fn malformed_json_is_json_error() {
+        let err = RuleLoader::load_from_str("[{").unwrap_err();
+        assert!(matches!(err, RuleEngineError::Json(_)));
+    }
+
+    #[test]
+    fn missing_file_is_io_error() {
+        let err = RuleLoader::load_from_file(&data_file("missing.json")).unwrap_err();
+        assert!(matches!(err, RuleEngineError::Io(_)));
+    }
+
     #[test]
     fn empty_json_returns_empty_list() {
         let rules = RuleLoader::load_from_str("[]").unwrap();
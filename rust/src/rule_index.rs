@@ -1,14 +1,51 @@
 use std::collections::HashMap;
 
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
 use crate::aho_corasick::AhoCorasick;
-use crate::rule::{Operator, Rule, UrlPart, URL_PART_COUNT};
-use crate::trie::Trie;
-use crate::url::ParsedUrl;
+use crate::rule::{Condition, Operator, Rule, UrlPart, URL_PART_COUNT};
+use crate::trie::{RadixTrie, Trie};
+use crate::url::{host_suffix_key, is_label_boundary, HostCase, ParsedUrl};
+
+/// One outcome reported by a sub-index lookup.
+enum SlotHit {
+    /// An indexed condition of the rule is satisfied.
+    Satisfied(u32),
+    /// An index-served negated condition of the rule matched.
+    Disqualified(u32),
+}
+
+/// Flag on rule IDs stored in the equals maps and tries marking them as
+/// coming from a negated condition, whose match disqualifies the rule.
+const NEGATED: u32 = 1 << 31;
+
+impl SlotHit {
+    /// Decodes a rule ID stored in an equals map or trie.
+    fn from_stored(id: u32) -> Self {
+        if id & NEGATED == 0 {
+            SlotHit::Satisfied(id)
+        } else {
+            SlotHit::Disqualified(id & !NEGATED)
+        }
+    }
+}
 
-/// Dense array-based container tracking how many non-negated conditions
+/// Dense array-based container tracking how many indexed conditions
 /// are satisfied per rule.
+///
+/// Rule IDs are also recorded in a `touched` list the first time their count
+/// leaves zero, so resets and candidate enumeration only visit rules that
+/// actually fired.
 pub struct CandidateResult {
     satisfied_counts: Vec<u32>,
+    touched: Vec<u32>,
+    /// Scratch buffer of contains-condition IDs hit during one search.
+    contains_hits: Vec<u32>,
+    /// Per-rule flag set when an index-served negated condition matched.
+    disqualified_flags: Vec<bool>,
+    /// IDs with a set flag, so resets only clear what was set.
+    disqualified: Vec<u32>,
 }
 
 impl CandidateResult {
@@ -16,26 +53,75 @@ impl CandidateResult {
     pub fn new() -> Self {
         Self {
             satisfied_counts: Vec::new(),
+            touched: Vec::new(),
+            contains_hits: Vec::new(),
+            disqualified_flags: Vec::new(),
+            disqualified: Vec::new(),
         }
     }
 
     /// Ensures the internal buffer is at least `n` elements, growing but
     /// never shrinking. Resets all counts to zero.
     pub fn ensure_capacity_and_reset(&mut self, n: usize) {
+        for &id in &self.touched {
+            self.satisfied_counts[id as usize] = 0;
+        }
+        self.touched.clear();
+        for &id in &self.disqualified {
+            self.disqualified_flags[id as usize] = false;
+        }
+        self.disqualified.clear();
         if self.satisfied_counts.len() < n {
             self.satisfied_counts.resize(n, 0);
-        } else {
-            self.satisfied_counts[..n].fill(0);
+            self.disqualified_flags.resize(n, false);
         }
     }
 
     fn increment(&mut self, rule_id: u32) {
-        self.satisfied_counts[rule_id as usize] += 1;
+        let count = &mut self.satisfied_counts[rule_id as usize];
+        if *count == 0 {
+            self.touched.push(rule_id);
+        }
+        *count += 1;
+    }
+
+    fn apply(&mut self, hit: SlotHit) {
+        match hit {
+            SlotHit::Satisfied(id) => self.increment(id),
+            SlotHit::Disqualified(id) => self.disqualify(id),
+        }
+    }
+
+    fn disqualify(&mut self, rule_id: u32) {
+        let flag = &mut self.disqualified_flags[rule_id as usize];
+        if !*flag {
+            *flag = true;
+            self.disqualified.push(rule_id);
+        }
+    }
+
+    /// Returns the number of rules the buffer can track without growing.
+    pub fn capacity(&self) -> usize {
+        self.satisfied_counts.len()
     }
 
-    /// Returns `true` if all non-negated conditions for the given rule have been satisfied.
+    /// Returns the IDs of rules with at least one satisfied condition, in the
+    /// order they were first satisfied.
+    pub fn touched(&self) -> &[u32] {
+        &self.touched
+    }
+
+    /// Returns `true` if all non-negated conditions for the given rule have
+    /// been satisfied and none of its index-served negated conditions matched.
     pub fn all_satisfied(&self, rule_id: u32, non_negated_counts: &[u32]) -> bool {
         self.satisfied_counts[rule_id as usize] == non_negated_counts[rule_id as usize]
+            && !self.is_disqualified(rule_id)
+    }
+
+    /// Returns `true` if an index-served negated condition of the rule
+    /// matched, so the rule cannot match whatever else holds.
+    pub fn is_disqualified(&self, rule_id: u32) -> bool {
+        self.disqualified_flags[rule_id as usize]
     }
 
     /// Returns `true` if the rule has at least one satisfied condition.
@@ -44,49 +130,195 @@ impl CandidateResult {
     }
 }
 
+impl Default for CandidateResult {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read-only summary of the index's size, broken down per `UrlPart` ordinal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexMemoryReport {
+    /// Distinct keys in each equals map.
+    pub equals_keys: [usize; URL_PART_COUNT],
+    /// Rule IDs stored across all keys of each equals map.
+    pub equals_rule_ids: [usize; URL_PART_COUNT],
+    /// Nodes (including the root) in each starts-with trie.
+    pub starts_with_nodes: [usize; URL_PART_COUNT],
+    /// Nodes (including the root) in each reversed ends-with trie.
+    pub ends_with_nodes: [usize; URL_PART_COUNT],
+    /// Nodes (including the root) in each reversed host-suffix trie.
+    pub host_suffix_nodes: [usize; URL_PART_COUNT],
+    /// States (including the root) in each contains automaton, negated
+    /// Contains automata included.
+    pub contains_states: [usize; URL_PART_COUNT],
+}
+
+impl IndexMemoryReport {
+    /// Total trie nodes across all starts-with, ends-with and host-suffix tries.
+    pub fn total_trie_nodes(&self) -> usize {
+        self.starts_with_nodes.iter().sum::<usize>()
+            + self.ends_with_nodes.iter().sum::<usize>()
+            + self.host_suffix_nodes.iter().sum::<usize>()
+    }
+
+    /// Total Aho-Corasick states across all contains automata.
+    pub fn total_contains_states(&self) -> usize {
+        self.contains_states.iter().sum()
+    }
+
+    /// Total distinct equals keys across all parts.
+    pub fn total_equals_keys(&self) -> usize {
+        self.equals_keys.iter().sum()
+    }
+
+    /// Lower-bound estimate in bytes, dominated by the 128-slot ASCII
+    /// transition table carried by every starts-with trie node and automaton
+    /// state. The path-compressed suffix tries have no such table and are
+    /// left out.
+    pub fn estimated_table_bytes(&self) -> usize {
+        let row = std::mem::size_of::<[u32; 128]>();
+        (self.starts_with_nodes.iter().sum::<usize>() + self.total_contains_states()) * row
+    }
+}
+
+/// Decoded conditions get their own sub-indexes after the raw ones.
+const INDEX_SLOTS: usize = 2 * URL_PART_COUNT;
+
+fn slot(cond: &Condition) -> usize {
+    cond.part.ordinal() + if cond.decoded { URL_PART_COUNT } else { 0 }
+}
+
 /// Indexes non-negated rule conditions by (UrlPart, Operator) for fast lookup.
+///
+/// Conditions whose operator is not indexable (see `Operator::is_indexable`)
+/// are left to direct evaluation and do not count towards a rule's total.
+#[derive(Serialize, Deserialize)]
 pub struct RuleIndex {
-    equals_indexes: [HashMap<String, Box<[u32]>>; URL_PART_COUNT],
-    starts_with_indexes: [Trie<u32>; URL_PART_COUNT],
-    ends_with_indexes: [Trie<u32>; URL_PART_COUNT],
-    contains_ac_indexes: [AhoCorasick<u32>; URL_PART_COUNT],
+    equals_indexes: [HashMap<String, Box<[u32]>>; INDEX_SLOTS],
+    starts_with_indexes: [Trie<u32>; INDEX_SLOTS],
+    /// Keyed by reversed suffixes, which rarely branch, so path compression
+    /// saves most of the nodes; likewise for host suffixes.
+    ends_with_indexes: [RadixTrie<u32>; INDEX_SLOTS],
+    /// Automaton values are contains-condition IDs, so repeated or multiple
+    /// hits of one condition can be collapsed before counting.
+    contains_ac_indexes: [AhoCorasick<u32>; INDEX_SLOTS],
+    /// Negated Contains patterns; values are dense rule IDs to disqualify.
+    negated_contains_ac_indexes: [AhoCorasick<u32>; INDEX_SLOTS],
+    /// Contains-condition ID -> dense rule ID.
+    contains_rule_ids: Vec<u32>,
+    /// Longest EndsWith key per part in bytes; bounds the suffix reversal.
+    max_ends_with_len: [usize; INDEX_SLOTS],
+    host_suffix_indexes: [RadixTrie<u32>; INDEX_SLOTS],
+    max_host_suffix_len: [usize; INDEX_SLOTS],
 
     rule_ids: HashMap<usize, u32>, // rule index in original list -> dense ID
     rule_count: usize,
     non_negated_counts: Vec<u32>,
-    has_equals: [bool; URL_PART_COUNT],
-    has_starts_with: [bool; URL_PART_COUNT],
-    has_ends_with: [bool; URL_PART_COUNT],
-    has_contains: [bool; URL_PART_COUNT],
+    has_equals: [bool; INDEX_SLOTS],
+    has_starts_with: [bool; INDEX_SLOTS],
+    has_ends_with: [bool; INDEX_SLOTS],
+    has_host_suffix: [bool; INDEX_SLOTS],
+    has_contains: [bool; INDEX_SLOTS],
+    has_negated_contains: [bool; INDEX_SLOTS],
+    /// Whether negated conditions of unindexed rules are served too.
+    index_negations: bool,
+    /// How host operands were case-folded.
+    host_case: HostCase,
 }
 
 impl RuleIndex {
     /// Builds the index from a list of rules.
     ///
-    /// Rules are identified by their position in the input list.
+    /// Rules are identified by their position in the input list. Disabled
+    /// rules keep their ID but contribute no indexed conditions.
     pub fn new(rules: &[Rule]) -> Self {
+        Self::build(rules, false, HostCase::Unicode)
+    }
+
+    /// Like [`new`](Self::new), but also serves the
+    /// [indexable](Condition::is_negated_indexable) negated conditions of
+    /// unindexed rules, disqualifying those rules when a condition matches.
+    pub fn new_with_negations(rules: &[Rule]) -> Self {
+        Self::build(rules, true, HostCase::Unicode)
+    }
+
+    /// Builds the index, serving negated conditions as
+    /// [`new_with_negations`](Self::new_with_negations) does when
+    /// `index_negations` is set, and folding host operands as `host_case`
+    /// folds parsed hosts (see [`HostCase`]).
+    pub(crate) fn build(rules: &[Rule], index_negations: bool, host_case: HostCase) -> Self {
         let rule_count = rules.len();
         let mut non_negated_counts = vec![0u32; rule_count];
 
-        let mut equals_indexes: [HashMap<String, Vec<u32>>; URL_PART_COUNT] =
+        let mut equals_indexes: [HashMap<String, Vec<u32>>; INDEX_SLOTS] =
             std::array::from_fn(|_| HashMap::new());
-        let mut starts_with_indexes: [Trie<u32>; URL_PART_COUNT] =
+        let mut starts_with_indexes: [Trie<u32>; INDEX_SLOTS] =
             std::array::from_fn(|_| Trie::new());
-        let mut ends_with_indexes: [Trie<u32>; URL_PART_COUNT] =
-            std::array::from_fn(|_| Trie::new());
-        let mut contains_ac_indexes: [AhoCorasick<u32>; URL_PART_COUNT] =
-            std::array::from_fn(|_| AhoCorasick::new());
+        let mut ends_with_indexes: [RadixTrie<u32>; INDEX_SLOTS] =
+            std::array::from_fn(|_| RadixTrie::new());
+        let mut max_ends_with_len = [0usize; INDEX_SLOTS];
+        let mut host_suffix_indexes: [RadixTrie<u32>; INDEX_SLOTS] =
+            std::array::from_fn(|_| RadixTrie::new());
+        let mut max_host_suffix_len = [0usize; INDEX_SLOTS];
+        let mut contains_patterns: [Vec<(String, u32)>; INDEX_SLOTS] =
+            std::array::from_fn(|_| Vec::new());
+        let mut negated_contains_patterns: [Vec<(String, u32)>; INDEX_SLOTS] =
+            std::array::from_fn(|_| Vec::new());
+        let mut contains_rule_ids = Vec::new();
 
         let mut rule_ids = HashMap::with_capacity(rule_count * 2);
+        assert!(rule_count < NEGATED as usize, "too many rules to index");
 
         for (i, rule) in rules.iter().enumerate() {
             let id = i as u32;
             rule_ids.insert(i, id);
+            if !rule.enabled {
+                continue;
+            }
 
             for cond in &rule.conditions {
-                if !cond.negated {
+                let cond = &*cond.normalized(host_case);
+                let p = slot(cond);
+                if Self::serves(index_negations, rule, cond) {
+                    // A match disqualifies the rule rather than counting.
+                    let tagged = id | NEGATED;
+                    match cond.operator {
+                        Operator::Contains => {
+                            negated_contains_patterns[p].push((cond.value.clone(), id));
+                        }
+                        Operator::ContainsAny => {
+                            for v in &cond.values {
+                                negated_contains_patterns[p].push((v.clone(), id));
+                            }
+                        }
+                        Operator::Equals => {
+                            equals_indexes[p].entry(cond.value.clone()).or_default().push(tagged);
+                        }
+                        Operator::IsEmpty => {
+                            equals_indexes[p].entry(String::new()).or_default().push(tagged);
+                        }
+                        Operator::InList => {
+                            for member in cond.list_values() {
+                                equals_indexes[p].entry(member.to_string()).or_default().push(tagged);
+                            }
+                        }
+                        Operator::StartsWith => starts_with_indexes[p].insert(&cond.value, tagged),
+                        Operator::EndsWith => {
+                            let reversed: String = cond.value.chars().rev().collect();
+                            ends_with_indexes[p].insert(&reversed, tagged);
+                            max_ends_with_len[p] = max_ends_with_len[p].max(cond.value.len());
+                        }
+                        Operator::HostSuffix => {
+                            let key = host_suffix_key(&cond.value);
+                            let reversed: String = key.chars().rev().collect();
+                            host_suffix_indexes[p].insert(&reversed, tagged);
+                            max_host_suffix_len[p] = max_host_suffix_len[p].max(key.len());
+                        }
+                        _ => unreachable!("see Condition::is_negated_indexable"),
+                    }
+                } else if cond.is_indexed() {
                     non_negated_counts[i] += 1;
-                    let p = cond.part.ordinal();
                     match cond.operator {
                         Operator::Equals => {
                             equals_indexes[p]
@@ -97,29 +329,74 @@ impl RuleIndex {
                         Operator::StartsWith => {
                             starts_with_indexes[p].insert(&cond.value, id);
                         }
+                        Operator::Matches => {
+                            // Pre-filter only; the engine checks the suffix.
+                            starts_with_indexes[p].insert(&cond.prefix, id);
+                        }
                         Operator::EndsWith => {
                             let reversed: String = cond.value.chars().rev().collect();
                             ends_with_indexes[p].insert(&reversed, id);
+                            max_ends_with_len[p] = max_ends_with_len[p].max(cond.value.len());
+                        }
+                        Operator::HostSuffix => {
+                            let key = host_suffix_key(&cond.value);
+                            let reversed: String = key.chars().rev().collect();
+                            host_suffix_indexes[p].insert(&reversed, id);
+                            max_host_suffix_len[p] = max_host_suffix_len[p].max(key.len());
                         }
                         Operator::Contains => {
-                            contains_ac_indexes[p].insert(&cond.value, id);
+                            let cond_id = contains_rule_ids.len() as u32;
+                            contains_rule_ids.push(id);
+                            contains_patterns[p].push((cond.value.clone(), cond_id));
+                        }
+                        Operator::ContainsAny => {
+                            let cond_id = contains_rule_ids.len() as u32;
+                            contains_rule_ids.push(id);
+                            for v in &cond.values {
+                                contains_patterns[p].push((v.clone(), cond_id));
+                            }
+                        }
+                        Operator::IsEmpty => {
+                            equals_indexes[p].entry(String::new()).or_default().push(id);
+                        }
+                        Operator::InList => {
+                            // Members are deduplicated, so at most one key can
+                            // match and the condition counts once.
+                            for member in cond.list_values() {
+                                equals_indexes[p].entry(member.to_string()).or_default().push(id);
+                            }
+                        }
+                        Operator::Glob
+                        | Operator::ContainsAtLeast
+                        | Operator::ExtensionIn
+                        | Operator::SegmentEquals
+                        | Operator::HostPattern
+                        | Operator::EqualsPart
+                        | Operator::ParamAbsent
+                        | Operator::HasSegment
+                        | Operator::NumInRange => {
+                            unreachable!("non-indexable operators are evaluated directly")
                         }
                     }
                 }
             }
         }
 
-        for ac in &mut contains_ac_indexes {
-            ac.build();
-        }
+        let contains_ac_indexes: [AhoCorasick<u32>; INDEX_SLOTS] =
+            contains_patterns.map(AhoCorasick::from_patterns);
+        let negated_contains_ac_indexes: [AhoCorasick<u32>; INDEX_SLOTS] =
+            negated_contains_patterns.map(AhoCorasick::from_patterns);
 
         let has_equals = std::array::from_fn(|p| !equals_indexes[p].is_empty());
         let has_starts_with = std::array::from_fn(|p| !starts_with_indexes[p].is_empty());
         let has_ends_with = std::array::from_fn(|p| !ends_with_indexes[p].is_empty());
+        let has_host_suffix = std::array::from_fn(|p| !host_suffix_indexes[p].is_empty());
         let has_contains = std::array::from_fn(|p| !contains_ac_indexes[p].is_empty());
+        let has_negated_contains =
+            std::array::from_fn(|p| !negated_contains_ac_indexes[p].is_empty());
 
         // Freeze equals indexes: Vec<u32> → Box<[u32]>
-        let equals_indexes: [HashMap<String, Box<[u32]>>; URL_PART_COUNT] =
+        let equals_indexes: [HashMap<String, Box<[u32]>>; INDEX_SLOTS] =
             std::array::from_fn(|p| {
                 std::mem::take(&mut equals_indexes[p])
                     .into_iter()
@@ -132,31 +409,122 @@ impl RuleIndex {
             starts_with_indexes,
             ends_with_indexes,
             contains_ac_indexes,
+            negated_contains_ac_indexes,
+            contains_rule_ids,
+            max_ends_with_len,
+            host_suffix_indexes,
+            max_host_suffix_len,
             rule_ids,
             rule_count,
             non_negated_counts,
             has_equals,
             has_starts_with,
             has_ends_with,
+            has_host_suffix,
             has_contains,
+            has_negated_contains,
+            index_negations,
+            host_case,
         }
     }
 
+    /// Returns `true` if the index serves this negated condition of `rule`
+    /// by disqualifying the rule when it matches, so the engine need not
+    /// check it directly.
+    ///
+    /// Negated `Contains` conditions are always served; other indexable
+    /// ones only with [`new_with_negations`](Self::new_with_negations) and
+    /// only for [unindexed](Rule::is_unindexed) rules.
+    pub fn serves_negated(&self, rule: &Rule, cond: &Condition) -> bool {
+        Self::serves(self.index_negations, rule, cond)
+    }
+
+    fn serves(index_negations: bool, rule: &Rule, cond: &Condition) -> bool {
+        cond.is_negated_indexed()
+            || (index_negations && cond.is_negated_indexable() && rule.is_unindexed())
+    }
+
+    /// Rebuilds the index for `rules` with the same options.
+    pub fn rebuilt(&self, rules: &[Rule]) -> Self {
+        Self::build(rules, self.index_negations, self.host_case)
+    }
+
+    /// Returns the case folding applied to host operands.
+    pub(crate) fn host_case(&self) -> HostCase {
+        self.host_case
+    }
+
+    /// Returns `true` if negated conditions of unindexed rules are served.
+    pub(crate) fn index_negations(&self) -> bool {
+        self.index_negations
+    }
+
     /// Returns the dense integer ID assigned to the rule at the given index.
     pub fn rule_id(&self, rule_index: usize) -> u32 {
         self.rule_ids[&rule_index]
     }
 
+    /// Like [`rule_id`](Self::rule_id), but `None` for a rule the index does
+    /// not know.
+    pub fn get_rule_id(&self, rule_index: usize) -> Option<u32> {
+        self.rule_ids.get(&rule_index).copied()
+    }
+
     /// Returns the number of rules in the index.
     pub fn rule_count(&self) -> usize {
         self.rule_count
     }
 
-    /// Returns the non-negated condition counts per rule.
+    /// Returns the longest suffix, in bytes, that a query may reverse into
+    /// its scratch buffer.
+    pub fn max_reverse_len(&self) -> usize {
+        self.max_ends_with_len
+            .iter()
+            .chain(&self.max_host_suffix_len)
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Reports node, state and entry counts for every sub-index.
+    pub fn memory_report(&self) -> IndexMemoryReport {
+        // Raw and decoded slots of a part are reported together.
+        let per_part = |f: &dyn Fn(usize) -> usize| -> [usize; URL_PART_COUNT] {
+            std::array::from_fn(|p| f(p) + f(p + URL_PART_COUNT))
+        };
+        IndexMemoryReport {
+            equals_keys: per_part(&|p| self.equals_indexes[p].len()),
+            equals_rule_ids: per_part(&|p| {
+                self.equals_indexes[p].values().map(|ids| ids.len()).sum()
+            }),
+            starts_with_nodes: per_part(&|p| self.starts_with_indexes[p].node_count()),
+            ends_with_nodes: per_part(&|p| self.ends_with_indexes[p].node_count()),
+            host_suffix_nodes: per_part(&|p| self.host_suffix_indexes[p].node_count()),
+            contains_states: per_part(&|p| {
+                self.contains_ac_indexes[p].state_count()
+                    + self.negated_contains_ac_indexes[p].state_count()
+            }),
+        }
+    }
+
+    /// Returns the number of indexed (non-negated, indexable) conditions per rule.
     pub fn non_negated_counts(&self) -> &[u32] {
         &self.non_negated_counts
     }
 
+    /// Returns the sorted dense IDs of rules with at least one indexed
+    /// condition satisfied by the URL.
+    ///
+    /// These are candidates, not confirmed matches: a rule may still have
+    /// unsatisfied indexed conditions, or negated and non-indexable
+    /// conditions that must be checked separately. Rules without any indexed
+    /// condition never appear.
+    pub fn candidate_ids(&self, url: &ParsedUrl) -> Vec<u32> {
+        let mut ids = self.query_candidates(url).touched().to_vec();
+        ids.sort_unstable();
+        ids
+    }
+
     /// Queries the index for all non-negated conditions that match the URL.
     ///
     /// Returns a `CandidateResult` that must be used before the next call.
@@ -177,41 +545,128 @@ impl RuleIndex {
     ) {
         candidates.ensure_capacity_and_reset(self.rule_count);
 
-        for part in UrlPart::ALL {
-            let p = part.ordinal();
-            let value = url.part(part);
+        let mut contains_hits = std::mem::take(&mut candidates.contains_hits);
+        for p in 0..INDEX_SLOTS {
+            self.query_slot(p, url, &mut contains_hits, reverse_buf, &mut |hit| {
+                candidates.apply(hit);
+            });
+        }
+        candidates.contains_hits = contains_hits;
+    }
 
-            if self.has_equals[p] {
-                if let Some(ids) = self.equals_indexes[p].get(value) {
-                    for &id in &**ids {
-                        candidates.increment(id);
-                    }
-                }
-            }
+    /// Like [`query_candidates_into`](Self::query_candidates_into), but
+    /// queries the per-part sub-indexes concurrently on the current rayon
+    /// pool, then merges their hits into `candidates`.
+    ///
+    /// Produces exactly the same candidates. Each part gets its own scratch
+    /// buffers, so this allocates per call; it only pays off when single-URL
+    /// latency matters and several parts have large indexes.
+    pub fn query_candidates_parallel_into(&self, url: &ParsedUrl, candidates: &mut CandidateResult) {
+        candidates.ensure_capacity_and_reset(self.rule_count);
 
-            if self.has_starts_with[p] {
-                self.starts_with_indexes[p]
-                    .find_prefixes_of_bytes(value.as_bytes(), &mut |&id| {
-                        candidates.increment(id);
-                    });
-            }
+        let per_slot: Vec<Vec<SlotHit>> = (0..INDEX_SLOTS)
+            .into_par_iter()
+            .filter(|&p| self.slot_active(p))
+            .map(|p| {
+                let mut hits = Vec::new();
+                self.query_slot(p, url, &mut Vec::new(), &mut Vec::new(), &mut |hit| {
+                    hits.push(hit);
+                });
+                hits
+            })
+            .collect();
+        for hit in per_slot.into_iter().flatten() {
+            candidates.apply(hit);
+        }
+    }
+
+    fn slot_active(&self, p: usize) -> bool {
+        self.has_equals[p]
+            || self.has_starts_with[p]
+            || self.has_ends_with[p]
+            || self.has_host_suffix[p]
+            || self.has_contains[p]
+            || self.has_negated_contains[p]
+    }
+
+    /// Runs every sub-index of one slot against the URL, reporting each
+    /// satisfied condition once and each disqualified rule at least once.
+    fn query_slot(
+        &self,
+        p: usize,
+        url: &ParsedUrl,
+        contains_hits: &mut Vec<u32>,
+        reverse_buf: &mut Vec<u8>,
+        report: &mut impl FnMut(SlotHit),
+    ) {
+        let part = UrlPart::ALL[p % URL_PART_COUNT];
+        let value = if p < URL_PART_COUNT { url.part(part) } else { url.decoded_part(part) };
 
-            if self.has_ends_with[p] {
-                // Reuse reverse_buf instead of allocating Vec<char> each call
-                reverse_buf.clear();
-                reverse_buf.extend(value.bytes().rev());
-                self.ends_with_indexes[p]
-                    .find_prefixes_of_bytes(reverse_buf, &mut |&id| {
-                        candidates.increment(id);
-                    });
+        if self.has_equals[p]
+            && let Some(ids) = self.equals_indexes[p].get(value)
+        {
+            for &id in &**ids {
+                report(SlotHit::from_stored(id));
             }
+        }
+
+        if self.has_starts_with[p] {
+            self.starts_with_indexes[p]
+                .find_prefixes_of_bytes(value.as_bytes(), &mut |&id| {
+                    report(SlotHit::from_stored(id));
+                });
+        }
 
-            if self.has_contains[p] {
-                self.contains_ac_indexes[p].search_bytes(value, &mut |&id| {
-                    candidates.increment(id);
+        if self.has_ends_with[p] {
+            // Only the last max_ends_with_len bytes can match any key, so
+            // reverse just that suffix into the reusable buffer.
+            let bytes = value.as_bytes();
+            let suffix = &bytes[bytes.len() - bytes.len().min(self.max_ends_with_len[p])..];
+            reverse_buf.clear();
+            reverse_buf.extend(suffix.iter().rev());
+            self.ends_with_indexes[p]
+                .find_prefixes_of_bytes(reverse_buf, &mut |&id| {
+                    report(SlotHit::from_stored(id));
                 });
+        }
+
+        if self.has_host_suffix[p] {
+            let bytes = value.as_bytes();
+            let suffix = &bytes[bytes.len() - bytes.len().min(self.max_host_suffix_len[p])..];
+            reverse_buf.clear();
+            reverse_buf.extend(suffix.iter().rev());
+            self.host_suffix_indexes[p].find_prefixes_of_bytes_with_depth(
+                reverse_buf,
+                &mut |&id, depth| {
+                    if is_label_boundary(value, depth) {
+                        report(SlotHit::from_stored(id));
+                    }
+                },
+            );
+        }
+
+        if self.has_contains[p] {
+            // A condition may hit several times (repeated occurrences or
+            // several ContainsAny values) but must count only once.
+            contains_hits.clear();
+            self.contains_ac_indexes[p].search_bytes(value, &mut |&cond_id| {
+                contains_hits.push(cond_id);
+            });
+            contains_hits.sort_unstable();
+            contains_hits.dedup();
+            for &cond_id in contains_hits.iter() {
+                report(SlotHit::Satisfied(self.contains_rule_ids[cond_id as usize]));
             }
         }
+
+        if self.has_negated_contains[p] {
+            // A matching negated Contains rules its rule out outright, as do
+            // negated IDs in the other sub-indexes, sparing the engine a
+            // direct check of every such rule.
+            self.negated_contains_ac_indexes[p].search_bytes(value, &mut |&id| {
+                report(SlotHit::Disqualified(id));
+            });
+        }
     }
 }
 
@@ -276,6 +731,76 @@ mod tests {
         assert!(candidates.is_candidate(index.rule_id(0)));
     }
 
+    #[test]
+    fn host_values_match_case_insensitively() {
+        let rules = vec![
+            rule("host", vec![cond(UrlPart::Host, Operator::EndsWith, ".CA")]),
+            rule("contains", vec![cond(UrlPart::Host, Operator::Contains, "Example")]),
+            rule("path", vec![cond(UrlPart::Path, Operator::EndsWith, ".CA")]),
+        ];
+        let index = RuleIndex::new(&rules);
+
+        let candidates =
+            index.query_candidates(&ParsedUrl::new("shop.example.ca", "/x.ca", "x.ca", ""));
+        assert!(candidates.is_candidate(0));
+        assert!(candidates.is_candidate(1));
+        assert!(!candidates.is_candidate(2));
+    }
+
+    #[test]
+    fn ends_with_on_very_long_value_reverses_only_key_length() {
+        let rules = vec![
+            rule("html", vec![cond(UrlPart::Query, Operator::EndsWith, ".html")]),
+            rule("l", vec![cond(UrlPart::Query, Operator::EndsWith, "l")]),
+            rule("htm", vec![cond(UrlPart::Query, Operator::EndsWith, ".htm")]),
+        ];
+        let index = RuleIndex::new(&rules);
+
+        let long_query = format!("{}page.html", "q=x&".repeat(250_000));
+        let mut candidates = CandidateResult::new();
+        let mut reverse_buf = Vec::new();
+        index.query_candidates_into(
+            &ParsedUrl::new("x.com", "/", "", long_query.as_str()),
+            &mut candidates,
+            &mut reverse_buf,
+        );
+
+        assert!(candidates.is_candidate(0));
+        assert!(candidates.is_candidate(1));
+        assert!(!candidates.is_candidate(2));
+        assert_eq!(".html".len(), reverse_buf.len());
+    }
+
+    #[test]
+    fn ends_with_shorter_value_than_longest_key() {
+        let rules = vec![
+            rule("long", vec![cond(UrlPart::Host, Operator::EndsWith, ".example.co.uk")]),
+            rule("short", vec![cond(UrlPart::Host, Operator::EndsWith, ".uk")]),
+        ];
+        let index = RuleIndex::new(&rules);
+
+        let candidates = index.query_candidates(&ParsedUrl::new("a.uk", "/", "", ""));
+        assert!(!candidates.is_candidate(0));
+        assert!(candidates.is_candidate(1));
+    }
+
+    #[test]
+    fn host_suffix_match_on_label_boundary() {
+        let r = rule("hs", vec![cond(UrlPart::Host, Operator::HostSuffix, "example.com")]);
+        let rules = vec![r];
+        let index = RuleIndex::new(&rules);
+
+        for (host, expected) in [
+            ("example.com", true),
+            ("a.example.com", true),
+            ("notexample.com", false),
+            ("com", false),
+        ] {
+            let candidates = index.query_candidates(&ParsedUrl::new(host, "/", "", ""));
+            assert_eq!(expected, candidates.is_candidate(index.rule_id(0)), "host {}", host);
+        }
+    }
+
     #[test]
     fn contains_match() {
         let r = rule("ct", vec![cond(UrlPart::Path, Operator::Contains, "sport")]);
@@ -291,6 +816,85 @@ mod tests {
         assert!(candidates.is_candidate(index.rule_id(0)));
     }
 
+    #[test]
+    fn negated_contains_disqualifies_rule() {
+        let rules = vec![
+            rule("a", vec![
+                cond(UrlPart::Host, Operator::Equals, "x.com"),
+                neg_cond(UrlPart::Path, Operator::Contains, "tmp"),
+            ]),
+            rule("b", vec![neg_cond(UrlPart::Path, Operator::Contains, "tmp")]),
+        ];
+        let index = RuleIndex::new(&rules);
+        let counts = index.non_negated_counts();
+
+        let mut candidates = index.query_candidates(&ParsedUrl::new("x.com", "/tmp/tmp", "", ""));
+        assert!(candidates.is_disqualified(0));
+        assert!(candidates.is_disqualified(1));
+        assert!(!candidates.all_satisfied(0, counts));
+
+        index.query_candidates_into(
+            &ParsedUrl::new("x.com", "/home", "", ""),
+            &mut candidates,
+            &mut Vec::new(),
+        );
+        assert!(!candidates.is_disqualified(0));
+        assert!(candidates.all_satisfied(0, counts));
+        assert!(candidates.all_satisfied(1, counts));
+    }
+
+    #[test]
+    fn negated_exact_conditions_of_unindexed_rules_disqualify() {
+        let rules = vec![
+            rule("a", vec![
+                cond(UrlPart::Host, Operator::Equals, "x.com"),
+                neg_cond(UrlPart::Host, Operator::Equals, "x.com"),
+            ]),
+            rule("b", vec![neg_cond(UrlPart::Path, Operator::StartsWith, "/tmp")]),
+            rule("c", vec![neg_cond(UrlPart::Host, Operator::HostSuffix, "x.com")]),
+        ];
+        let plain = RuleIndex::new(&rules).query_candidates(&ParsedUrl::new("x.com", "/tmp", "", ""));
+        assert!(!plain.is_disqualified(1) && !plain.is_disqualified(2));
+
+        let index = RuleIndex::new_with_negations(&rules);
+        assert_eq!(&[1, 0, 0], index.non_negated_counts());
+
+        let candidates = index.query_candidates(&ParsedUrl::new("a.x.com", "/tmp/1", "", ""));
+        assert!(!candidates.is_disqualified(0));
+        assert!(candidates.is_disqualified(1));
+        assert!(candidates.is_disqualified(2));
+
+        // Rule "a" has an indexed condition, so its negated one is checked
+        // directly, and only if "a" becomes a candidate.
+        let candidates = index.query_candidates(&ParsedUrl::new("x.com", "/home", "", ""));
+        assert!(!candidates.is_disqualified(0));
+        assert!(!candidates.is_disqualified(1));
+        assert!(candidates.is_candidate(0));
+    }
+
+    #[test]
+    fn repeated_contains_hits_count_once() {
+        let r = rule("ct", vec![cond(UrlPart::Path, Operator::Contains, "a")]);
+        let rules = vec![r];
+        let index = RuleIndex::new(&rules);
+
+        let candidates = index.query_candidates(&ParsedUrl::new("x.com", "/banana", "", ""));
+        assert!(candidates.all_satisfied(index.rule_id(0), index.non_negated_counts()));
+    }
+
+    #[test]
+    fn contains_any_counts_once() {
+        let any = Condition::new(UrlPart::Path, Operator::ContainsAny, "", false)
+            .with_values(["sport", "news", "finance"]);
+        let rules = vec![rule("any", vec![any])];
+        let index = RuleIndex::new(&rules);
+        assert_eq!(1, index.non_negated_counts()[0]);
+
+        let candidates =
+            index.query_candidates(&ParsedUrl::new("x.com", "/news/sport", "", ""));
+        assert!(candidates.all_satisfied(index.rule_id(0), index.non_negated_counts()));
+    }
+
     #[test]
     fn negated_conditions_not_indexed() {
         let r = rule(
@@ -305,6 +909,69 @@ mod tests {
         assert!(!candidates.is_candidate(index.rule_id(0)));
     }
 
+    #[test]
+    fn glob_conditions_not_indexed() {
+        let r = rule("glob", vec![cond(UrlPart::Path, Operator::Glob, "/api/*")]);
+        let rules = vec![r];
+        let index = RuleIndex::new(&rules);
+
+        let candidates =
+            index.query_candidates(&ParsedUrl::new("x.com", "/api/users", "users", ""));
+        assert!(!candidates.is_candidate(index.rule_id(0)));
+        assert_eq!(0, index.non_negated_counts()[0]);
+    }
+
+    #[test]
+    fn in_list_members_share_rule_id() {
+        let r = rule("list", vec![cond(UrlPart::Host, Operator::InList, "a.com,b.com,b.com")]);
+        let rules = vec![r];
+        let index = RuleIndex::new(&rules);
+
+        let candidates = index.query_candidates(&ParsedUrl::new("b.com", "/", "", ""));
+        assert!(candidates.all_satisfied(index.rule_id(0), index.non_negated_counts()));
+        assert_eq!(2, index.memory_report().equals_rule_ids[UrlPart::Host.ordinal()]);
+    }
+
+    #[test]
+    fn candidate_ids_lists_rules_with_any_fired_condition() {
+        let rules = vec![
+            rule("host", vec![cond(UrlPart::Host, Operator::Equals, "example.com")]),
+            rule("miss", vec![cond(UrlPart::Host, Operator::Equals, "other.com")]),
+            rule(
+                "partial",
+                vec![
+                    cond(UrlPart::Path, Operator::StartsWith, "/api"),
+                    cond(UrlPart::Query, Operator::Contains, "absent"),
+                ],
+            ),
+            rule("neg", vec![neg_cond(UrlPart::Path, Operator::Contains, "admin")]),
+            rule("ew", vec![cond(UrlPart::Host, Operator::EndsWith, ".com")]),
+        ];
+        let index = RuleIndex::new(&rules);
+
+        let ids = index.candidate_ids(&ParsedUrl::new("example.com", "/api/v1", "v1", "q=1"));
+        assert_eq!(vec![index.rule_id(0), index.rule_id(2), index.rule_id(4)], ids);
+    }
+
+    #[test]
+    fn reused_candidate_result_resets_touched_rules() {
+        let rules = vec![
+            rule("a", vec![cond(UrlPart::Host, Operator::Equals, "a.com")]),
+            rule("b", vec![cond(UrlPart::Host, Operator::Equals, "b.com")]),
+        ];
+        let index = RuleIndex::new(&rules);
+        let mut candidates = CandidateResult::new();
+        let mut reverse_buf = Vec::new();
+
+        index.query_candidates_into(&ParsedUrl::new("a.com", "/", "", ""), &mut candidates, &mut reverse_buf);
+        assert_eq!(&[0], candidates.touched());
+
+        index.query_candidates_into(&ParsedUrl::new("b.com", "/", "", ""), &mut candidates, &mut reverse_buf);
+        assert!(!candidates.is_candidate(0));
+        assert!(candidates.is_candidate(1));
+        assert_eq!(&[1], candidates.touched());
+    }
+
     #[test]
     fn multiple_rules_multiple_operators() {
         let r1 = rule("r1", vec![cond(UrlPart::Host, Operator::Equals, "example.com")]);
@@ -335,6 +1002,102 @@ mod tests {
         assert!(candidates.is_candidate(index.rule_id(0)));
     }
 
+    #[test]
+    fn memory_report_counts_sub_indexes() {
+        let rules = vec![
+            rule("a", vec![cond(UrlPart::Host, Operator::Equals, "a.com")]),
+            rule("b", vec![cond(UrlPart::Host, Operator::Equals, "a.com")]),
+            rule("c", vec![cond(UrlPart::Host, Operator::Equals, "c.com")]),
+            rule("d", vec![cond(UrlPart::Path, Operator::StartsWith, "/api")]),
+            rule("e", vec![cond(UrlPart::Path, Operator::StartsWith, "/api/v2")]),
+            rule("f", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+            rule("g", vec![cond(UrlPart::Query, Operator::Contains, "lang")]),
+            rule("h", vec![neg_cond(UrlPart::File, Operator::Contains, "tmp")]),
+        ];
+        let index = RuleIndex::new(&rules);
+        let report = index.memory_report();
+
+        let host = UrlPart::Host.ordinal();
+        let path = UrlPart::Path.ordinal();
+        let query = UrlPart::Query.ordinal();
+        let file = UrlPart::File.ordinal();
+
+        assert_eq!(2, report.equals_keys[host]);
+        assert_eq!(3, report.equals_rule_ids[host]);
+        assert_eq!(2, report.total_equals_keys());
+        // Per-part counts include the empty decoded sub-index's root.
+        assert_eq!(8 + 1, report.starts_with_nodes[path]);
+        // ".ca" reversed is a single compressed edge.
+        assert_eq!(2 + 1, report.ends_with_nodes[host]);
+        assert_eq!(5 + 3, report.contains_states[query]);
+        // The negated "tmp" lives in the negated automaton: 3 states + 4 roots.
+        assert_eq!(3 + 4, report.contains_states[file]);
+
+        // Every sub-index owns a root even when empty.
+        assert_eq!(7 + 1 + 3 * INDEX_SLOTS, report.total_trie_nodes());
+        assert_eq!(4 + 3 + 2 * INDEX_SLOTS, report.total_contains_states());
+        assert_eq!(
+            (7 + INDEX_SLOTS + report.total_contains_states()) * 512,
+            report.estimated_table_bytes()
+        );
+    }
+
+    #[test]
+    fn parallel_query_matches_sequential_query() {
+        let words = ["shop", "api", "v1", "static", "img", "login"];
+        let tlds = [".com", ".org", ".ca"];
+        let mut rules = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            for tld in tlds {
+                let host = format!("{word}{tld}");
+                let path = format!("/{word}");
+                rules.push(rule(&format!("{host}-eq"), vec![
+                    cond(UrlPart::Host, Operator::Equals, &host),
+                    cond(UrlPart::Path, Operator::StartsWith, &path),
+                ]));
+                rules.push(rule(&format!("{host}-suffix"), vec![
+                    cond(UrlPart::Host, Operator::HostSuffix, &host),
+                    neg_cond(UrlPart::Query, Operator::Contains, words[(i + 1) % words.len()]),
+                ]));
+            }
+            rules.push(rule(&format!("{word}-contains"), vec![
+                cond(UrlPart::Query, Operator::Contains, word),
+                cond(UrlPart::File, Operator::EndsWith, ".html").with_decoded(true),
+            ]));
+        }
+        let index = RuleIndex::new(&rules);
+
+        let mut sequential = CandidateResult::new();
+        let mut parallel = CandidateResult::new();
+        let mut reverse_buf = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            for tld in tlds {
+                for other in words {
+                    let host = format!("www.{word}{tld}");
+                    let path = format!("/{other}/page.html");
+                    let query = format!("q={}&{other}=1", words[(i + 2) % words.len()]);
+                    let url = ParsedUrl::new(&host, &path, "page.html", &query);
+
+                    index.query_candidates_into(&url, &mut sequential, &mut reverse_buf);
+                    index.query_candidates_parallel_into(&url, &mut parallel);
+
+                    let mut expected = sequential.touched().to_vec();
+                    let mut actual = parallel.touched().to_vec();
+                    expected.sort_unstable();
+                    actual.sort_unstable();
+                    assert_eq!(expected, actual, "{host}{path}?{query}");
+                    for id in 0..rules.len() as u32 {
+                        assert_eq!(
+                            sequential.all_satisfied(id, index.non_negated_counts()),
+                            parallel.all_satisfied(id, index.non_negated_counts())
+                        );
+                        assert_eq!(sequential.is_disqualified(id), parallel.is_disqualified(id));
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn concurrent_queries_return_correct_results() {
         use std::sync::Arc;
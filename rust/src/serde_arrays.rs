@@ -0,0 +1,48 @@
+//! Serde adapters for the fixed 128-slot ASCII transition tables used by
+//! `Trie` and `AhoCorasick`. Serde only derives arrays up to 32 elements, so
+//! tables are written as flat `u32` sequences and length-checked on read.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+const ROW: usize = 128;
+
+/// (De)serializes a single `[u32; 128]` row.
+pub(crate) mod row {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(row: &[u32; ROW], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(row.iter())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u32; ROW], D::Error> {
+        let flat = Vec::<u32>::deserialize(d)?;
+        let len = flat.len();
+        flat.try_into()
+            .map_err(|_| D::Error::custom(format!("expected {} slots, found {}", ROW, len)))
+    }
+}
+
+/// (De)serializes a `Vec<[u32; 128]>` table as one flat sequence.
+pub(crate) mod rows {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(rows: &[[u32; ROW]], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(rows.iter().flatten())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<[u32; ROW]>, D::Error> {
+        let flat = Vec::<u32>::deserialize(d)?;
+        if flat.len() % ROW != 0 {
+            return Err(D::Error::custom(format!(
+                "table length {} is not a multiple of {}",
+                flat.len(),
+                ROW
+            )));
+        }
+        Ok(flat
+            .chunks_exact(ROW)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect())
+    }
+}
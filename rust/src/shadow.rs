@@ -0,0 +1,139 @@
+use crate::engine::Matcher;
+use crate::rule::{Condition, Operator, Rule};
+use crate::url::{host_suffix_key, matches_host_suffix, HostCase};
+
+/// Returns `true` if every URL matching `rule` also matches `broader`.
+///
+/// Sound but incomplete: each condition of `broader` must be implied by a
+/// single condition of `rule` on the same part, so `false` only means no
+/// implication was found. Host operands are folded as `host_case` folds
+/// parsed hosts.
+pub(crate) fn rule_implies(rule: &Rule, broader: &Rule, host_case: HostCase) -> bool {
+    broader
+        .conditions
+        .iter()
+        .all(|b| rule.conditions.iter().any(|c| condition_implies(c, b, host_case)))
+}
+
+/// Returns `true` if `cond` holding guarantees that `other` holds.
+fn condition_implies(cond: &Condition, other: &Condition, host_case: HostCase) -> bool {
+    let (cond, other) = (&*cond.normalized(host_case), &*other.normalized(host_case));
+    if cond.part != other.part || cond.decoded != other.decoded || cond.param != other.param {
+        return false;
+    }
+    if cond == other {
+        return true;
+    }
+    if cond.negated
+        || cond.operator == Operator::EqualsPart
+        || other.operator == Operator::EqualsPart
+    {
+        // Cross-part conditions depend on values this check never sees.
+        return false;
+    }
+
+    if other.negated && other.param.is_some() {
+        // One pinned parameter value says nothing about the other pairs.
+        return false;
+    }
+
+    // Exact values pin the part down, so `other` can simply be evaluated.
+    let holds = |value: &str| Matcher::compile(other).is_match(value) != other.negated;
+    match cond.operator {
+        Operator::Equals => return holds(&cond.value),
+        Operator::InList => return cond.list_values().into_iter().all(holds),
+        _ => {}
+    }
+    if other.negated {
+        return false;
+    }
+
+    let value = other.value.as_str();
+    match (cond.operator, other.operator) {
+        (Operator::StartsWith, Operator::StartsWith) => cond.value.starts_with(value),
+        (Operator::EndsWith, Operator::EndsWith) => cond.value.ends_with(value),
+        (Operator::StartsWith | Operator::EndsWith | Operator::Contains, Operator::Contains) => {
+            cond.value.contains(value)
+        }
+        (Operator::HostSuffix, Operator::HostSuffix) => {
+            matches_host_suffix(host_suffix_key(&cond.value), value)
+        }
+        (Operator::HostSuffix, Operator::EndsWith) => host_suffix_key(&cond.value).ends_with(value),
+        (Operator::HostSuffix, Operator::Contains) => host_suffix_key(&cond.value).contains(value),
+        (Operator::Matches, Operator::StartsWith) => cond.prefix.starts_with(value),
+        (Operator::Matches, Operator::EndsWith) => cond.suffix.ends_with(value),
+        (Operator::Matches, Operator::Contains) => {
+            cond.prefix.contains(value) || cond.suffix.contains(value)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::UrlPart;
+
+    fn cond(part: UrlPart, op: Operator, value: &str) -> Condition {
+        Condition::new(part, op, value, false)
+    }
+
+    fn implies(cond: &Condition, other: &Condition) -> bool {
+        condition_implies(cond, other, HostCase::Unicode)
+    }
+
+    #[test]
+    fn equals_implies_matching_suffix() {
+        let eq = cond(UrlPart::Host, Operator::Equals, "example.com");
+        assert!(implies(&eq, &cond(UrlPart::Host, Operator::EndsWith, ".com")));
+        assert!(!implies(&eq, &cond(UrlPart::Host, Operator::EndsWith, ".org")));
+        assert!(!implies(&eq, &cond(UrlPart::Path, Operator::EndsWith, ".com")));
+    }
+
+    #[test]
+    fn equals_decides_negated_conditions() {
+        let eq = cond(UrlPart::Path, Operator::Equals, "/home");
+        let not_admin = Condition::new(UrlPart::Path, Operator::StartsWith, "/admin", true);
+        assert!(implies(&eq, &not_admin));
+    }
+
+    #[test]
+    fn param_conditions_only_imply_same_param() {
+        let id = cond(UrlPart::Query, Operator::Equals, "prod_1").with_param("id");
+        let prefix = cond(UrlPart::Query, Operator::StartsWith, "prod_").with_param("id");
+        let not_other = Condition::new(UrlPart::Query, Operator::Equals, "x", true).with_param("id");
+        assert!(implies(&id, &prefix));
+        assert!(!implies(&id, &prefix.clone().with_param("sku")));
+        assert!(!implies(&id, &cond(UrlPart::Query, Operator::StartsWith, "prod_")));
+        assert!(!implies(&id, &not_other));
+    }
+
+    #[test]
+    fn longer_prefix_implies_shorter() {
+        let long = cond(UrlPart::Path, Operator::StartsWith, "/api/v2");
+        assert!(implies(&long, &cond(UrlPart::Path, Operator::StartsWith, "/api")));
+        assert!(!implies(&cond(UrlPart::Path, Operator::StartsWith, "/api"), &long));
+    }
+
+    #[test]
+    fn host_suffix_respects_label_boundaries() {
+        let sub = cond(UrlPart::Host, Operator::HostSuffix, "a.example.com");
+        let parent = cond(UrlPart::Host, Operator::HostSuffix, "example.com");
+        let other = cond(UrlPart::Host, Operator::HostSuffix, "ample.com");
+        assert!(implies(&sub, &parent));
+        assert!(!implies(&sub, &other));
+    }
+
+    #[test]
+    fn conditionless_rule_is_implied_by_anything() {
+        let specific = Rule::new(
+            "s",
+            1,
+            vec![cond(UrlPart::Host, Operator::Equals, "example.com")],
+            "s",
+        );
+        let catch_all = Rule::new("c", 1, vec![], "c");
+        assert!(rule_implies(&specific, &catch_all, HostCase::Unicode));
+        assert!(!rule_implies(&catch_all, &specific, HostCase::Unicode));
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::engine::RuleEngine;
+
+/// A [`RuleEngine`] that can be replaced while other threads evaluate with
+/// it, for reloading rules without stopping traffic.
+///
+/// Readers call [`load`](Self::load) per evaluation, which is cheap and
+/// lock-free; a reader keeps the engine it loaded until the guard drops,
+/// so an evaluation never sees half of a swap. [`store`](Self::store)
+/// replaces the engine for every later `load`, and the old one is freed
+/// once its last guard is gone.
+pub struct SharedEngine {
+    current: ArcSwap<RuleEngine>,
+}
+
+/// The engine a reader loaded from a [`SharedEngine`]; derefs to
+/// `Arc<RuleEngine>`.
+pub type EngineGuard = arc_swap::Guard<Arc<RuleEngine>>;
+
+impl SharedEngine {
+    /// Wraps `engine` for sharing.
+    pub fn new(engine: RuleEngine) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(engine),
+        }
+    }
+
+    /// Returns the current engine. Meant to be held briefly, e.g. for one
+    /// evaluation or one batch; holding many guards at once is slower.
+    pub fn load(&self) -> EngineGuard {
+        self.current.load()
+    }
+
+    /// Atomically replaces the engine. Build the new engine before calling,
+    /// so readers are never blocked on compilation.
+    pub fn store(&self, engine: RuleEngine) {
+        self.current.store(Arc::new(engine));
+    }
+}
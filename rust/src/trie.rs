@@ -1,10 +1,14 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 const ASCII_SIZE: usize = 128;
 const NO_NODE: u32 = u32::MAX;
 
 /// Arena-based node for the trie.
+#[derive(Serialize, Deserialize)]
 struct TrieNode<V: Clone> {
+    #[serde(with = "crate::serde_arrays::row")]
     ascii: [u32; ASCII_SIZE],
     extended: Option<HashMap<char, u32>>,
     values: Vec<V>,
@@ -64,6 +68,7 @@ impl<V: Clone> TrieNode<V> {
 ///
 /// Uses arena-based storage with `Vec<TrieNode>` and `u32` indices.
 /// Supports prefix queries via `find_prefixes_of`.
+#[derive(Serialize, Deserialize)]
 pub struct Trie<V: Clone> {
     nodes: Vec<TrieNode<V>>,
     empty_key_values: Vec<V>,
@@ -73,10 +78,8 @@ pub struct Trie<V: Clone> {
 impl<V: Clone> Trie<V> {
     /// Creates a new empty trie.
     pub fn new() -> Self {
-        let mut nodes = Vec::new();
-        nodes.push(TrieNode::new()); // root = index 0
         Self {
-            nodes,
+            nodes: vec![TrieNode::new()], // root = index 0
             empty_key_values: Vec::new(),
             has_keys: false,
         }
@@ -87,6 +90,23 @@ impl<V: Clone> Trie<V> {
         !self.has_keys && self.empty_key_values.is_empty()
     }
 
+    /// Returns the number of arena nodes, including the root.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Lower-bound estimate in bytes of the node arena and child maps,
+    /// excluding stored values.
+    pub fn estimated_bytes(&self) -> usize {
+        let extended: usize = self
+            .nodes
+            .iter()
+            .filter_map(|n| n.extended.as_ref())
+            .map(|m| m.capacity() * std::mem::size_of::<(char, u32)>())
+            .sum();
+        self.nodes.len() * std::mem::size_of::<TrieNode<V>>() + extended
+    }
+
     /// Inserts a value associated with the given key.
     pub fn insert(&mut self, key: &str, value: V) {
         self.has_keys = true;
@@ -120,6 +140,23 @@ impl<V: Clone> Trie<V> {
         }
     }
 
+    /// Returns how many leading chars of `input` follow some path in the
+    /// trie, whether or not a value is stored along it.
+    pub fn longest_common_prefix_len(&self, input: &str) -> usize {
+        let mut current: u32 = 0;
+        let mut len = 0;
+        for c in input.chars() {
+            match self.nodes[current as usize].child(c) {
+                Some(next) => {
+                    current = next;
+                    len += 1;
+                }
+                None => break,
+            }
+        }
+        len
+    }
+
     /// Byte-oriented prefix search. Iterates `&[u8]` directly, using the
     /// inline ASCII array for bytes < 128 and returning immediately for
     /// bytes >= 128 (since all indexed patterns are ASCII).
@@ -143,6 +180,37 @@ impl<V: Clone> Trie<V> {
         }
     }
 
+    /// Byte-oriented prefix search that also reports the matched key length.
+    ///
+    /// The second callback argument is the length in bytes of the key that
+    /// matched, so callers can tell a full `/api/users` match from a shorter
+    /// `/api` match on the same input. Values stored under the empty key are
+    /// reported with length 0. Like `find_prefixes_of_bytes`, the search
+    /// stops at the first byte >= 128.
+    pub fn find_prefixes_of_bytes_with_depth(
+        &self,
+        input: &[u8],
+        callback: &mut impl FnMut(&V, usize),
+    ) {
+        for v in &self.empty_key_values {
+            callback(v, 0);
+        }
+        let mut current: u32 = 0;
+        for (i, &b) in input.iter().enumerate() {
+            if b >= 128 {
+                return;
+            }
+            let next = self.nodes[current as usize].child_byte(b);
+            if next == NO_NODE {
+                return;
+            }
+            current = next;
+            for v in &self.nodes[current as usize].values {
+                callback(v, i + 1);
+            }
+        }
+    }
+
     /// Invokes the callback for each value whose key is a prefix of the input char slice.
     pub fn find_prefixes_of_chars(&self, input: &[char], callback: &mut impl FnMut(&V)) {
         for v in &self.empty_key_values {
@@ -168,6 +236,58 @@ impl<V: Clone> Trie<V> {
         self.find_prefixes_of(input, &mut |v| result.push(v.clone()));
         result
     }
+
+    /// Invokes the callback for every stored value together with its full key.
+    ///
+    /// Keys are reconstructed by a depth-first walk over the arena. Values
+    /// under the empty key come first; children are then visited in ASCII
+    /// order followed by non-ASCII characters in code point order, so the
+    /// enumeration is deterministic. The walk uses an explicit stack, so
+    /// arbitrarily long keys cannot overflow the call stack.
+    pub fn for_each_entry<'a>(&'a self, callback: &mut impl FnMut(&str, &'a V)) {
+        for v in &self.empty_key_values {
+            callback("", v);
+        }
+        let mut key = String::new();
+        // (node, key length before its char, its char); the root has none.
+        let mut stack: Vec<(u32, usize, Option<char>)> = vec![(0, 0, None)];
+        let mut children: Vec<(char, u32)> = Vec::new();
+        while let Some((node, len, c)) = stack.pop() {
+            key.truncate(len);
+            let n = &self.nodes[node as usize];
+            if let Some(c) = c {
+                key.push(c);
+                for v in &n.values {
+                    callback(&key, v);
+                }
+            }
+
+            children.clear();
+            children.extend(
+                n.ascii
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &child)| child != NO_NODE)
+                    .map(|(b, &child)| (b as u8 as char, child)),
+            );
+            if let Some(ext) = &n.extended {
+                let start = children.len();
+                children.extend(ext.iter().map(|(&c, &id)| (c, id)));
+                children[start..].sort_unstable();
+            }
+            // Reversed so the first child is popped first.
+            let len = key.len();
+            stack.extend(children.iter().rev().map(|&(c, child)| (child, len, Some(c))));
+        }
+    }
+
+    /// Returns an iterator over `(key, value)` pairs in `for_each_entry` order.
+    pub fn iter_keys(&self) -> impl Iterator<Item = (String, &V)> {
+        let mut entries = Vec::new();
+        self.for_each_entry(&mut |key, v| entries.push((key.to_string(), v)));
+        entries.into_iter()
+    }
+
 }
 
 impl<V: Clone> Default for Trie<V> {
@@ -176,6 +296,310 @@ impl<V: Clone> Default for Trie<V> {
     }
 }
 
+/// Node of a [`SparseTrie`]: children sorted by character.
+#[derive(Serialize, Deserialize)]
+struct SparseNode<V: Clone> {
+    children: Vec<(char, u32)>,
+    values: Vec<V>,
+}
+
+impl<V: Clone> SparseNode<V> {
+    fn new() -> Self {
+        Self {
+            children: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    fn child(&self, c: char) -> Option<u32> {
+        self.children
+            .binary_search_by_key(&c, |&(k, _)| k)
+            .ok()
+            .map(|i| self.children[i].1)
+    }
+}
+
+/// A [`Trie`] variant whose nodes keep their children in a small sorted
+/// `Vec<(char, u32)>` instead of a 128-slot ASCII table.
+///
+/// Lookups binary-search each node's children, so it is slower than `Trie`
+/// on ASCII-heavy keys, but it needs far less memory when keys are mostly
+/// non-ASCII (e.g. CJK paths), where `Trie` nodes carry an all-empty ASCII
+/// table. Prefix queries return the same values as `Trie`.
+#[derive(Serialize, Deserialize)]
+pub struct SparseTrie<V: Clone> {
+    nodes: Vec<SparseNode<V>>,
+    empty_key_values: Vec<V>,
+    has_keys: bool,
+}
+
+impl<V: Clone> SparseTrie<V> {
+    /// Creates a new empty trie.
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![SparseNode::new()],
+            empty_key_values: Vec::new(),
+            has_keys: false,
+        }
+    }
+
+    /// Returns `true` if this trie contains no entries.
+    pub fn is_empty(&self) -> bool {
+        !self.has_keys && self.empty_key_values.is_empty()
+    }
+
+    /// Returns the number of arena nodes, including the root.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Lower-bound estimate in bytes of the node arena and child lists,
+    /// excluding stored values; comparable to [`Trie::estimated_bytes`].
+    pub fn estimated_bytes(&self) -> usize {
+        let children: usize = self.nodes.iter().map(|n| n.children.capacity()).sum();
+        self.nodes.len() * std::mem::size_of::<SparseNode<V>>()
+            + children * std::mem::size_of::<(char, u32)>()
+    }
+
+    /// Inserts a value associated with the given key.
+    pub fn insert(&mut self, key: &str, value: V) {
+        self.has_keys = true;
+        if key.is_empty() {
+            self.empty_key_values.push(value);
+            return;
+        }
+        let mut current = 0usize;
+        for c in key.chars() {
+            let children = &self.nodes[current].children;
+            current = match children.binary_search_by_key(&c, |&(k, _)| k) {
+                Ok(i) => children[i].1 as usize,
+                Err(i) => {
+                    let id = self.nodes.len();
+                    self.nodes[current].children.insert(i, (c, id as u32));
+                    self.nodes.push(SparseNode::new());
+                    id
+                }
+            };
+        }
+        self.nodes[current].values.push(value);
+    }
+
+    /// Invokes the callback for each value whose key is a prefix of the input.
+    pub fn find_prefixes_of(&self, input: &str, callback: &mut impl FnMut(&V)) {
+        for v in &self.empty_key_values {
+            callback(v);
+        }
+        let mut current: u32 = 0;
+        for c in input.chars() {
+            match self.nodes[current as usize].child(c) {
+                Some(next) => {
+                    current = next;
+                    for v in &self.nodes[current as usize].values {
+                        callback(v);
+                    }
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Returns all values whose keys are prefixes of the given input.
+    pub fn find_prefixes_of_collect(&self, input: &str) -> Vec<V> {
+        let mut result = Vec::new();
+        self.find_prefixes_of(input, &mut |v| result.push(v.clone()));
+        result
+    }
+}
+
+impl<V: Clone> Default for SparseTrie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Node of a [`RadixTrie`]: the bytes on the edge leading into it, and its
+/// children sorted by the first byte of their edge.
+#[derive(Serialize, Deserialize)]
+struct RadixNode<V: Clone> {
+    label: Box<[u8]>,
+    children: Vec<(u8, u32)>,
+    values: Vec<V>,
+}
+
+impl<V: Clone> RadixNode<V> {
+    fn new(label: &[u8]) -> Self {
+        Self {
+            label: label.into(),
+            children: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns the position of the child whose edge starts with `b`, or
+    /// where such a child would be inserted.
+    fn child_pos(&self, b: u8) -> Result<usize, usize> {
+        self.children.binary_search_by_key(&b, |&(k, _)| k)
+    }
+}
+
+/// A [`Trie`] variant with path compression: each chain of nodes that
+/// neither branch nor hold values collapses into a single edge labelled
+/// with the chain's bytes.
+///
+/// Long keys sharing few prefixes, such as reversed domain suffixes, then
+/// need about one node per key instead of one per character. Prefix
+/// queries return the same values, in the same order, as `Trie`.
+#[derive(Serialize, Deserialize)]
+pub struct RadixTrie<V: Clone> {
+    /// The root's label is empty; it holds the empty key's values.
+    nodes: Vec<RadixNode<V>>,
+    /// The root's ASCII children, mirrored into a table since every lookup
+    /// starts there.
+    #[serde(with = "crate::serde_arrays::row")]
+    root_ascii: [u32; ASCII_SIZE],
+}
+
+impl<V: Clone> RadixTrie<V> {
+    /// Creates a new empty trie.
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![RadixNode::new(&[])],
+            root_ascii: [NO_NODE; ASCII_SIZE],
+        }
+    }
+
+    /// Returns `true` if this trie contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1 && self.nodes[0].values.is_empty()
+    }
+
+    /// Returns the number of arena nodes, including the root.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Lower-bound estimate in bytes of the node arena, edge labels and
+    /// child lists, excluding stored values; comparable to
+    /// [`Trie::estimated_bytes`].
+    pub fn estimated_bytes(&self) -> usize {
+        let heap: usize = self
+            .nodes
+            .iter()
+            .map(|n| n.label.len() + n.children.capacity() * std::mem::size_of::<(u8, u32)>())
+            .sum();
+        self.nodes.len() * std::mem::size_of::<RadixNode<V>>()
+            + heap
+            + std::mem::size_of_val(&self.root_ascii)
+    }
+
+    /// Inserts a value associated with the given key.
+    pub fn insert(&mut self, key: &str, value: V) {
+        let mut rest = key.as_bytes();
+        let mut current = 0usize;
+        while let Some(&first) = rest.first() {
+            let pos = match self.nodes[current].child_pos(first) {
+                Ok(pos) => pos,
+                Err(i) => {
+                    let id = self.nodes.len();
+                    self.nodes[current].children.insert(i, (first, id as u32));
+                    self.nodes.push(RadixNode::new(rest));
+                    current = id;
+                    break;
+                }
+            };
+            let slot = self.nodes[current].children[pos].1 as usize;
+            let label = &self.nodes[slot].label;
+            let common = label.iter().zip(rest).take_while(|(a, b)| a == b).count();
+            if common < label.len() {
+                // Split the edge: a new node takes the shared bytes and
+                // adopts the old child under its remaining bytes.
+                let mid = self.nodes.len();
+                let tail: Box<[u8]> = label[common..].into();
+                let mut node = RadixNode::new(&label[..common]);
+                node.children.push((tail[0], slot as u32));
+                self.nodes[slot].label = tail;
+                self.nodes.push(node);
+                self.nodes[current].children[pos].1 = mid as u32;
+                current = mid;
+            } else {
+                current = slot;
+            }
+            rest = &rest[common..];
+        }
+        self.nodes[current].values.push(value);
+        if let Some(&first) = key.as_bytes().first()
+            && first.is_ascii()
+            && let Ok(pos) = self.nodes[0].child_pos(first)
+        {
+            self.root_ascii[first as usize] = self.nodes[0].children[pos].1;
+        }
+    }
+
+    /// Invokes the callback for each value whose key is a prefix of the input.
+    pub fn find_prefixes_of(&self, input: &str, callback: &mut impl FnMut(&V)) {
+        // UTF-8 keys that are byte prefixes of the input end on a char boundary.
+        self.walk(input.as_bytes(), false, &mut |v, _| callback(v));
+    }
+
+    /// Byte-oriented prefix search, stopping at the first byte >= 128 just
+    /// like [`Trie::find_prefixes_of_bytes`].
+    pub fn find_prefixes_of_bytes(&self, input: &[u8], callback: &mut impl FnMut(&V)) {
+        self.walk(input, true, &mut |v, _| callback(v));
+    }
+
+    /// Like [`Trie::find_prefixes_of_bytes_with_depth`], reporting the
+    /// length in bytes of each matched key.
+    pub fn find_prefixes_of_bytes_with_depth(
+        &self,
+        input: &[u8],
+        callback: &mut impl FnMut(&V, usize),
+    ) {
+        self.walk(input, true, callback);
+    }
+
+    /// Returns all values whose keys are prefixes of the given input.
+    pub fn find_prefixes_of_collect(&self, input: &str) -> Vec<V> {
+        let mut result = Vec::new();
+        self.find_prefixes_of(input, &mut |v| result.push(v.clone()));
+        result
+    }
+
+    fn walk(&self, input: &[u8], ascii_only: bool, callback: &mut impl FnMut(&V, usize)) {
+        let mut node = &self.nodes[0];
+        let mut depth = 0;
+        loop {
+            for v in &node.values {
+                callback(v, depth);
+            }
+            let Some(&b) = input.get(depth) else { return };
+            let child = if depth == 0 && b.is_ascii() {
+                self.root_ascii[b as usize]
+            } else {
+                match node.child_pos(b) {
+                    Ok(pos) => node.children[pos].1,
+                    Err(_) => NO_NODE,
+                }
+            };
+            if child == NO_NODE {
+                return;
+            }
+            node = &self.nodes[child as usize];
+            let end = depth + node.label.len();
+            let Some(span) = input.get(depth..end) else { return };
+            if span != &*node.label || (ascii_only && !span.is_ascii()) {
+                return;
+            }
+            depth = end;
+        }
+    }
+}
+
+impl<V: Clone> Default for RadixTrie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -311,6 +735,16 @@ mod tests {
         assert!(Trie::<u32>::new().is_empty());
     }
 
+    #[test]
+    fn node_count_counts_shared_prefixes_once() {
+        let mut trie = Trie::new();
+        assert_eq!(1, trie.node_count());
+        trie.insert("/api", 1u32);
+        trie.insert("/api/v2", 2u32);
+        trie.insert("", 3u32);
+        assert_eq!(8, trie.node_count());
+    }
+
     #[test]
     fn is_not_empty_after_insert() {
         let mut trie = Trie::new();
@@ -401,4 +835,198 @@ mod tests {
         trie.find_prefixes_of_bytes(input, &mut |v| result.push(*v));
         assert!(result.is_empty());
     }
+
+    #[test]
+    fn with_depth_reports_matched_key_lengths() {
+        let mut trie = Trie::new();
+        trie.insert("", 0u32);
+        trie.insert("/api", 1u32);
+        trie.insert("/api/users", 2u32);
+
+        let mut result = Vec::new();
+        trie.find_prefixes_of_bytes_with_depth(b"/api/users/123", &mut |v, depth| {
+            result.push((*v, depth))
+        });
+        assert_eq!(vec![(0, 0), (1, 4), (2, 10)], result);
+    }
+
+    #[test]
+    fn with_depth_distinguishes_full_from_partial_match() {
+        let mut trie = Trie::new();
+        trie.insert("/api", 1u32);
+        trie.insert("/api/users", 2u32);
+
+        let input = b"/api/users";
+        let mut full = Vec::new();
+        trie.find_prefixes_of_bytes_with_depth(input, &mut |v, depth| {
+            if depth == input.len() {
+                full.push(*v);
+            }
+        });
+        assert_eq!(vec![2], full);
+    }
+
+    #[test]
+    fn with_depth_stops_at_non_ascii() {
+        let mut trie = Trie::new();
+        trie.insert("ab", 1u32);
+        trie.insert("abc", 2u32);
+        let mut result = Vec::new();
+        trie.find_prefixes_of_bytes_with_depth(&[b'a', b'b', 0xFF, b'c'], &mut |v, depth| {
+            result.push((*v, depth))
+        });
+        assert_eq!(vec![(1, 2)], result);
+    }
+
+    #[test]
+    fn iter_keys_reconstructs_all_keys() {
+        let mut trie = Trie::new();
+        trie.insert("/api", 1u32);
+        trie.insert("/api/users", 2u32);
+        trie.insert("", 3u32);
+
+        let entries: Vec<(String, u32)> = trie.iter_keys().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(
+            vec![
+                ("".to_string(), 3),
+                ("/api".to_string(), 1),
+                ("/api/users".to_string(), 2),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn iter_keys_handles_branching_and_non_ascii() {
+        let mut trie = Trie::new();
+        trie.insert("ab", 1u32);
+        trie.insert("ac", 2u32);
+        trie.insert("a\u{00E9}", 3u32);
+        trie.insert("ab", 4u32);
+
+        let entries: Vec<(String, u32)> = trie.iter_keys().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(
+            vec![
+                ("ab".to_string(), 1),
+                ("ab".to_string(), 4),
+                ("ac".to_string(), 2),
+                ("a\u{00E9}".to_string(), 3),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn longest_common_prefix_len_follows_paths() {
+        let mut trie = Trie::new();
+        trie.insert("/api", 1u32);
+        trie.insert("/app", 2u32);
+        assert_eq!(4, trie.longest_common_prefix_len("/api/x"));
+        assert_eq!(3, trie.longest_common_prefix_len("/apx"));
+        // The leading `/` is shared by both keys.
+        assert_eq!(1, trie.longest_common_prefix_len("/xyz"));
+        assert_eq!(0, trie.longest_common_prefix_len("xyz"));
+
+        let mut accented = Trie::new();
+        accented.insert("\u{00E9}t\u{00E9}", 1u32);
+        assert_eq!(2, accented.longest_common_prefix_len("\u{00E9}tu"));
+    }
+
+    #[test]
+    fn sparse_trie_matches_trie_on_cjk_keys_with_less_memory() {
+        let keys = [
+            "/\u{65B0}\u{95FB}",
+            "/\u{65B0}\u{95FB}/\u{4F53}\u{80B2}",
+            "/\u{65B0}\u{95FB}/\u{8D22}\u{7ECF}",
+            "/\u{8D2D}\u{7269}/\u{624B}\u{673A}",
+            "/\u{8D2D}\u{7269}",
+            "",
+            "/\u{30CB}\u{30E5}\u{30FC}\u{30B9}",
+        ];
+        let mut dense = Trie::new();
+        let mut sparse = SparseTrie::new();
+        for (i, key) in keys.iter().enumerate() {
+            dense.insert(key, i as u32);
+            sparse.insert(key, i as u32);
+        }
+        assert_eq!(dense.node_count(), sparse.node_count());
+
+        let inputs = [
+            "/\u{65B0}\u{95FB}/\u{4F53}\u{80B2}/\u{8DB3}\u{7403}",
+            "/\u{8D2D}\u{7269}/\u{624B}",
+            "/\u{30CB}\u{30E5}\u{30FC}\u{30B9}",
+            "/x",
+            "",
+        ];
+        for input in inputs {
+            assert_eq!(dense.find_prefixes_of_collect(input), sparse.find_prefixes_of_collect(input));
+        }
+        assert!(sparse.estimated_bytes() < dense.estimated_bytes());
+    }
+
+    #[test]
+    fn radix_trie_matches_trie_on_deep_chains_with_fewer_nodes() {
+        let keys = [
+            "moc.elpmaxe.",
+            "moc.elpmaxe.swen.",
+            "moc.elpmaxe.swen.tropsdnaswenlacolyliad.",
+            "moc.elpmax",
+            "gro.gnitsetgnolyrevyrevdnagnolyrev.",
+            "",
+            "\u{00E9}t\u{00E9}.",
+        ];
+        let mut dense = Trie::new();
+        let mut radix = RadixTrie::new();
+        assert!(radix.is_empty());
+        for (i, key) in keys.iter().enumerate() {
+            dense.insert(key, i as u32);
+            radix.insert(key, i as u32);
+        }
+        dense.insert("moc.elpmaxe.", 99);
+        radix.insert("moc.elpmaxe.", 99);
+
+        let inputs = [
+            "moc.elpmaxe.swen.tropsdnaswenlacolyliad.www",
+            "moc.elpmaxe.swen",
+            "moc.elpmaxe",
+            "gro.gnitsetgnolyrevyrevdnagnolyrev.a",
+            "gro.gnitset",
+            "\u{00E9}t\u{00E9}.fr",
+            "",
+        ];
+        for input in inputs {
+            assert_eq!(dense.find_prefixes_of_collect(input), radix.find_prefixes_of_collect(input));
+            let (mut expected, mut actual) = (Vec::new(), Vec::new());
+            dense.find_prefixes_of_bytes_with_depth(input.as_bytes(), &mut |v, d| {
+                expected.push((*v, d))
+            });
+            radix.find_prefixes_of_bytes_with_depth(input.as_bytes(), &mut |v, d| {
+                actual.push((*v, d))
+            });
+            assert_eq!(expected, actual, "{input}");
+        }
+        assert_eq!(80, dense.node_count());
+        assert_eq!(7, radix.node_count());
+        assert!(radix.estimated_bytes() < dense.estimated_bytes() / 10);
+    }
+
+    #[test]
+    fn iter_keys_handles_very_deep_keys() {
+        let deep = "a".repeat(100_000);
+        let mut trie = Trie::new();
+        trie.insert(&deep, 1u32);
+        trie.insert("ab", 2u32);
+        let entries: Vec<(String, u32)> = trie.iter_keys().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(2, entries.len());
+        assert_eq!((deep, 1), entries[0]);
+        assert_eq!(("ab".to_string(), 2), entries[1]);
+    }
+
+    #[test]
+    fn for_each_entry_on_empty_trie() {
+        let trie: Trie<u32> = Trie::new();
+        let mut count = 0;
+        trie.for_each_entry(&mut |_, _| count += 1);
+        assert_eq!(0, count);
+    }
 }
@@ -0,0 +1,24 @@
+static PATTERN_0: std::sync::LazyLock<rule_engine::glob::HostPattern> =
+    std::sync::LazyLock::new(|| rule_engine::glob::HostPattern::new("api.*.example.com"));
+static PATTERN_1: std::sync::LazyLock<rule_engine::glob::GlobPattern> =
+    std::sync::LazyLock::new(|| rule_engine::glob::GlobPattern::new("/user/*/page?"));
+static PATTERN_2: std::sync::LazyLock<rule_engine::glob::HostPattern> =
+    std::sync::LazyLock::new(|| rule_engine::glob::HostPattern::new("*.example.com"));
+
+pub fn match_url(url: &rule_engine::url::ParsedUrl) -> Option<&'static str> {
+    // api
+    if PATTERN_0.is_match(url.host.as_str()) {
+        return Some("api");
+    }
+    // user-pages
+    if PATTERN_1.is_match(url.path.as_str()) {
+        return Some("user");
+    }
+    // not-user
+    if !(PATTERN_1.is_match(url.path.as_str()))
+        && PATTERN_2.is_match(url.host.as_str())
+    {
+        return Some("other");
+    }
+    None
+}
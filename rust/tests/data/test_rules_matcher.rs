@@ -0,0 +1,19 @@
+pub fn match_url(url: &rule_engine::url::ParsedUrl) -> Option<&'static str> {
+    // Canada Sport
+    if url.host.as_str().ends_with(".ca")
+        && url.path.as_str().contains("sport")
+    {
+        return Some("Canada Sport");
+    }
+    // Example Home
+    if url.host.as_str() == "example.com"
+        && url.path.as_str() == "/"
+    {
+        return Some("Example Home");
+    }
+    // Not Admin
+    if !(url.path.as_str().starts_with("/admin")) {
+        return Some("Not Admin");
+    }
+    None
+}
@@ -220,6 +220,7 @@ impl DataGenerator {
             UrlPart::Path => self.random_path_value(operator),
             UrlPart::File => self.random_file_value(operator),
             UrlPart::Query => self.random_query_value(),
+            _ => unreachable!("only the base URL parts are generated"),
         };
         Condition::new(part, operator, &value, false)
     }
@@ -230,6 +231,7 @@ impl DataGenerator {
             Operator::Contains => self.pick(BRAND_KEYWORDS).to_string(),
             Operator::StartsWith => self.pick(HOST_PREFIXES).to_string(),
             Operator::EndsWith => self.pick(TLDS).to_string(),
+            _ => unreachable!("only indexable operators are generated"),
         }
     }
 
@@ -239,6 +241,7 @@ impl DataGenerator {
             Operator::Contains => self.pick(PATH_KEYWORDS).to_string(),
             Operator::StartsWith => self.pick(PATH_DIRS).to_string(),
             Operator::EndsWith => format!("/{}", self.pick(PATH_KEYWORDS)),
+            _ => unreachable!("only indexable operators are generated"),
         }
     }
 
@@ -247,6 +250,7 @@ impl DataGenerator {
             Operator::Equals => format!("{}{}", self.pick(FILE_NAMES), self.pick(FILE_EXTENSIONS)),
             Operator::Contains | Operator::StartsWith => self.pick(FILE_NAMES).to_string(),
             Operator::EndsWith => self.pick(FILE_EXTENSIONS).to_string(),
+            _ => unreachable!("only indexable operators are generated"),
         }
     }
 
@@ -503,6 +507,7 @@ impl DataGenerator {
             UrlPart::Path => self.large_random_path_value(operator),
             UrlPart::File => self.large_random_file_value(operator),
             UrlPart::Query => self.pick(LARGE_QUERY_PARAMS).to_string(),
+            _ => unreachable!("only the base URL parts are generated"),
         };
         Condition::new(part, operator, &value, false)
     }
@@ -530,6 +535,7 @@ impl DataGenerator {
                     format!(".{}", self.generate_domain(idx))
                 }
             }
+            _ => unreachable!("only indexable operators are generated"),
         }
     }
 
@@ -539,6 +545,7 @@ impl DataGenerator {
             Operator::Contains => self.pick(LARGE_PATH_KEYWORDS).to_string(),
             Operator::StartsWith => self.pick(LARGE_PATH_SEGMENTS).to_string(),
             Operator::EndsWith => format!("/{}", self.pick(LARGE_PATH_KEYWORDS)),
+            _ => unreachable!("only indexable operators are generated"),
         }
     }
 
@@ -547,6 +554,7 @@ impl DataGenerator {
             Operator::Equals => format!("{}{}", self.pick(LARGE_FILE_NAMES), self.pick(LARGE_FILE_EXTENSIONS)),
             Operator::Contains | Operator::StartsWith => self.pick(LARGE_FILE_NAMES).to_string(),
             Operator::EndsWith => self.pick(LARGE_FILE_EXTENSIONS).to_string(),
+            _ => unreachable!("only indexable operators are generated"),
         }
     }
 
@@ -606,11 +614,12 @@ impl DataGenerator {
 
         // Host ends_with domain suffix (5,000)
         for i in 0..5_000 {
-            let domain = if i < DOMAINS.len() {
-                DOMAINS[i].to_string()
-            } else {
-                let idx = self.rng.gen_range(0..20_000);
-                self.generate_domain(idx)
+            let domain = match DOMAINS.get(i) {
+                Some(d) => d.to_string(),
+                None => {
+                    let idx = self.rng.gen_range(0..20_000);
+                    self.generate_domain(idx)
+                }
             };
             let value = format!(".{}", domain);
             rules.push(self.make_rule(&format!("host-suffix-{}", id), UrlPart::Host, Operator::EndsWith, &value));
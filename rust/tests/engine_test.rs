@@ -1,7 +1,12 @@
-use rule_engine::batch::BatchProcessor;
-use rule_engine::engine::RuleEngine;
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+use rule_engine::batch::{BatchProcessor, MatchStatus};
+use rule_engine::engine::{MatchObserver, OversizedPart, RuleEngine, TieBreak};
+use rule_engine::error::RuleEngineError;
 use rule_engine::rule::{Condition, Operator, Rule, RuleLoader, UrlPart};
-use rule_engine::url::{ParsedUrl, UrlParser};
+use rule_engine::shared::SharedEngine;
+use rule_engine::url::{HostCase, ParsedUrl, UrlParseError, UrlParser};
 
 // --- Helpers ---
 
@@ -19,15 +24,12 @@ fn neg_cond(part: UrlPart, op: Operator, value: &str) -> Condition {
 
 /// Shorthand to build a ParsedUrl with an auto-derived file component.
 fn url(host: &str, path: &str, query: &str) -> ParsedUrl {
-    let file = if !path.is_empty() {
-        match path.rfind('/') {
-            Some(pos) => &path[pos + 1..],
-            None => path,
-        }
-    } else {
-        ""
-    };
-    ParsedUrl::new(host, path, file, query)
+    ParsedUrl::builder()
+        .host(host)
+        .path(path)
+        .query(query)
+        .build()
+        .unwrap()
 }
 
 // ====================================================================
@@ -263,6 +265,33 @@ fn same_priority_uses_definition_order() {
     assert_eq!(Some("first-result"), result);
 }
 
+#[test]
+fn most_specific_tiebreak_prefers_more_conditions() {
+    let broad = rule(
+        "broad",
+        5,
+        "broad",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")],
+    );
+    let specific = rule(
+        "specific",
+        5,
+        "specific",
+        vec![
+            cond(UrlPart::Host, Operator::EndsWith, ".ca"),
+            cond(UrlPart::Path, Operator::StartsWith, "/sport"),
+        ],
+    );
+    let u = url("news.ca", "/sport/hockey", "");
+
+    let default = RuleEngine::new(vec![broad.clone(), specific.clone()]);
+    assert_eq!(Some("broad"), default.evaluate(&u));
+
+    let engine = RuleEngine::new_with_tiebreak(vec![broad, specific], TieBreak::MostSpecific);
+    assert_eq!(Some("specific"), engine.evaluate(&u));
+    assert_eq!(Some("broad"), engine.evaluate(&url("news.ca", "/weather", "")));
+}
+
 #[test]
 fn lower_priority_matches_when_higher_does_not() {
     let high = rule(
@@ -359,153 +388,2298 @@ fn file_part_matching() {
     );
 }
 
-// ====================================================================
-// BatchProcessorTest (5 tests)
-// ====================================================================
+#[test]
+fn glob_operator() {
+    let r = rule(
+        "glob",
+        1,
+        "matched",
+        vec![cond(UrlPart::Path, Operator::Glob, "/api/*/users")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("matched"),
+        engine.evaluate(&url("x.com", "/api/v2/users", ""))
+    );
+    assert_eq!(None, engine.evaluate(&url("x.com", "/api/v2/groups", "")));
+}
 
 #[test]
-fn processes_multiple_urls() {
-    let r1 = rule(
-        "ca-sport",
-        10,
-        "Canada Sport",
+fn glob_question_mark_matches_one_char() {
+    let r = rule(
+        "glob",
+        1,
+        "markup",
+        vec![cond(UrlPart::File, Operator::Glob, "index.??ml")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("markup"),
+        engine.evaluate(&url("x.com", "/index.html", ""))
+    );
+    assert_eq!(None, engine.evaluate(&url("x.com", "/index.htm", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/index.xhtml", "")));
+}
+
+#[test]
+fn glob_combined_with_indexed_condition() {
+    let r = rule(
+        "glob-host",
+        1,
+        "matched",
         vec![
             cond(UrlPart::Host, Operator::EndsWith, ".ca"),
-            cond(UrlPart::Path, Operator::Contains, "sport"),
+            cond(UrlPart::Path, Operator::Glob, "/shop/*"),
         ],
     );
-    let r2 = rule(
-        "example-home",
-        5,
-        "Example Home",
-        vec![
-            cond(UrlPart::Host, Operator::Equals, "example.com"),
-            cond(UrlPart::Path, Operator::Equals, "/"),
-        ],
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("matched"),
+        engine.evaluate(&url("store.ca", "/shop/item", ""))
     );
+    assert_eq!(None, engine.evaluate(&url("store.com", "/shop/item", "")));
+    assert_eq!(None, engine.evaluate(&url("store.ca", "/blog/item", "")));
+}
 
-    let engine = RuleEngine::new(vec![r1, r2]);
-    let processor = BatchProcessor::new(&engine);
+#[test]
+fn negated_glob() {
+    let r = rule(
+        "not-api",
+        1,
+        "not-api",
+        vec![neg_cond(UrlPart::Path, Operator::Glob, "/api/*")],
+    );
+    let engine = RuleEngine::new(vec![r]);
 
-    let lines: Vec<String> = vec![
-        "https://shop.example.ca/category/sport/items".to_string(),
-        "https://example.com/".to_string(),
-        "https://other.org/page".to_string(),
-    ];
+    assert_eq!(Some("not-api"), engine.evaluate(&url("x.com", "/home", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/api/v1", "")));
+}
 
-    let results = processor.process_lines(&lines);
+#[test]
+fn disabled_rule_never_matches() {
+    let mut r = rule(
+        "off",
+        1,
+        "off-result",
+        vec![cond(UrlPart::Host, Operator::Equals, "example.com")],
+    );
+    r.enabled = false;
+    let engine = RuleEngine::new(vec![r]);
 
-    assert_eq!(3, results.len());
-    assert_eq!("Canada Sport", results[0].result);
-    assert_eq!("Example Home", results[1].result);
-    assert_eq!("NO_MATCH", results[2].result);
+    assert_eq!(None, engine.evaluate(&url("example.com", "/", "")));
 }
 
 #[test]
-fn skips_blank_lines() {
-    let r = rule(
-        "r",
+fn disabled_negated_only_rule_never_matches() {
+    let mut r = rule(
+        "off",
         1,
-        "ok",
-        vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
+        "off-result",
+        vec![neg_cond(UrlPart::Path, Operator::StartsWith, "/admin")],
     );
+    r.enabled = false;
     let engine = RuleEngine::new(vec![r]);
-    let processor = BatchProcessor::new(&engine);
 
-    let lines: Vec<String> = vec![
-        "https://x.com/".to_string(),
-        "".to_string(),
-        "  ".to_string(),
-        "https://x.com/page".to_string(),
-    ];
-    let results = processor.process_lines(&lines);
-    assert_eq!(2, results.len());
+    assert_eq!(None, engine.evaluate(&url("x.com", "/home", "")));
 }
 
 #[test]
-fn handles_invalid_urls() {
+fn set_enabled_toggles_matching() {
+    let high = rule(
+        "high",
+        10,
+        "high-result",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let low = rule(
+        "low",
+        1,
+        "low-result",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let mut engine = RuleEngine::new(vec![high, low]);
+    let u = url("example.com", "/", "");
+
+    engine.set_enabled("high", false);
+    assert_eq!(Some("low-result"), engine.evaluate(&u));
+
+    engine.set_enabled("high", true);
+    assert_eq!(Some("high-result"), engine.evaluate(&u));
+}
+
+#[test]
+fn set_enabled_unknown_name_is_noop() {
     let r = rule(
         "r",
         1,
-        "ok",
+        "result",
         vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
     );
-    let engine = RuleEngine::new(vec![r]);
-    let processor = BatchProcessor::new(&engine);
+    let mut engine = RuleEngine::new(vec![r]);
 
-    let lines: Vec<String> = vec!["://bad-url".to_string()];
-    let results = processor.process_lines(&lines);
-    assert_eq!(1, results.len());
-    assert_eq!("INVALID_URL", results[0].result);
+    engine.set_enabled("missing", false);
+    assert_eq!(Some("result"), engine.evaluate(&url("x.com", "/", "")));
 }
 
 #[test]
-fn empty_input_returns_empty_results() {
-    let engine = RuleEngine::new(vec![]);
-    let processor = BatchProcessor::new(&engine);
-    let results = processor.process_lines(&[]);
-    assert!(results.is_empty());
+fn evaluate_first_uses_definition_order() {
+    let early_low = rule(
+        "early-low",
+        1,
+        "early-low-result",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let late_high = rule(
+        "late-high",
+        10,
+        "late-high-result",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let rules = vec![early_low, late_high];
+    let u = url("example.com", "/", "");
+
+    let unordered = RuleEngine::new_unordered(rules.clone());
+    assert_eq!(
+        Some("early-low"),
+        unordered.evaluate_first(&u).map(|r| r.name.as_str())
+    );
+
+    let prioritized = RuleEngine::new(rules);
+    assert_eq!(Some("late-high-result"), prioritized.evaluate(&u));
+    assert_eq!(
+        Some("late-high"),
+        prioritized.evaluate_first(&u).map(|r| r.name.as_str())
+    );
 }
 
 #[test]
-fn parallel_processing_preserves_order() {
+fn evaluate_first_skips_non_matching_rules() {
+    let miss = rule(
+        "miss",
+        1,
+        "miss-result",
+        vec![cond(UrlPart::Host, Operator::Equals, "other.com")],
+    );
+    let hit = rule(
+        "hit",
+        1,
+        "hit-result",
+        vec![neg_cond(UrlPart::Path, Operator::StartsWith, "/admin")],
+    );
+    let engine = RuleEngine::new_unordered(vec![miss, hit]);
+
+    assert_eq!(
+        Some("hit"),
+        engine
+            .evaluate_first(&url("example.com", "/", ""))
+            .map(|r| r.name.as_str())
+    );
+    assert!(engine.evaluate_first(&url("example.com", "/admin", "")).is_none());
+}
+
+#[test]
+fn set_enabled_preserves_definition_order() {
+    let first = rule(
+        "first",
+        1,
+        "first-result",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let second = rule(
+        "second",
+        10,
+        "second-result",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let mut engine = RuleEngine::new_unordered(vec![first, second]);
+    let u = url("example.com", "/", "");
+
+    engine.set_enabled("first", false);
+    engine.set_enabled("first", true);
+    assert_eq!(Some("first-result"), engine.evaluate(&u));
+}
+
+#[test]
+fn punycode_rule_matches_unicode_host() {
     let r = rule(
-        "host-match",
+        "idn",
         1,
-        "matched",
-        vec![cond(UrlPart::Host, Operator::Equals, "example.com")],
+        "cafe",
+        vec![cond(UrlPart::Host, Operator::Equals, "xn--caf-dma.com")],
     );
     let engine = RuleEngine::new(vec![r]);
-    let processor = BatchProcessor::new(&engine);
 
-    let urls: Vec<String> = (0..10_000)
-        .map(|i| format!("https://example.com/page/{}", i))
-        .collect();
+    let parsed = UrlParser::parse_idna("https://caf\u{00E9}.com/menu").unwrap();
+    assert_eq!(Some("cafe"), engine.evaluate(&parsed));
+}
 
-    let results = processor.process_lines(&urls);
+#[test]
+fn domain_part_matches_any_subdomain() {
+    let r = rule(
+        "google",
+        1,
+        "google",
+        vec![cond(UrlPart::Domain, Operator::Equals, "google.co.uk")],
+    );
+    let engine = RuleEngine::new(vec![r]);
 
-    assert_eq!(urls.len(), results.len());
-    for (i, result) in results.iter().enumerate() {
-        assert_eq!(
-            format!("https://example.com/page/{}", i),
-            result.url,
-            "Result at index {} has wrong URL",
-            i
-        );
-        assert_eq!("matched", result.result);
-    }
+    assert_eq!(
+        Some("google"),
+        engine.evaluate(&url("www.shop.google.co.uk", "/", ""))
+    );
+    assert_eq!(Some("google"), engine.evaluate(&url("google.co.uk", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("google.com", "/", "")));
 }
 
-// ====================================================================
-// AppTest (integration with test-rules.json)
-// ====================================================================
+#[test]
+fn tld_part_is_indexed_by_public_suffix() {
+    let rules = vec![
+        rule("uk", 2, "uk", vec![cond(UrlPart::Tld, Operator::Equals, "co.uk")]),
+        rule("com", 1, "com", vec![cond(UrlPart::Tld, Operator::InList, "com,net")]),
+    ];
+    let engine = RuleEngine::new(rules);
 
-const TEST_RULES_JSON: &str = include_str!("data/test-rules.json");
+    assert_eq!(Some("uk"), engine.evaluate(&url("shop.example.co.uk", "/", "")));
+    assert_eq!(Some("com"), engine.evaluate(&url("example.com", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("example.uk", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("10.0.0.1", "/", "")));
+}
 
 #[test]
-fn integration_test_with_resource_files() {
-    let rules = RuleLoader::load_from_str(TEST_RULES_JSON).unwrap();
-    let engine = RuleEngine::new(rules);
-    let processor = BatchProcessor::new(&engine);
+fn fully_qualified_host_matches_host_rules() {
+    let engine = RuleEngine::new(vec![
+        rule("exact", 2, "exact", vec![cond(UrlPart::Host, Operator::Equals, "example.com")]),
+        rule("com", 1, "com", vec![cond(UrlPart::Host, Operator::EndsWith, ".com")]),
+    ]);
+    let parsed = UrlParser::parse("https://example.com./").unwrap();
+    assert_eq!("example.com", parsed.host);
+    assert_eq!(Some("exact"), engine.evaluate(&parsed));
+    assert_eq!(Some("com"), engine.evaluate(&UrlParser::parse("https://www.example.com.").unwrap()));
+}
 
-    let lines: Vec<String> = vec![
-        "https://shop.example.ca/category/sport/items".to_string(),
-        "https://example.com/".to_string(),
-        "https://example.com/admin/panel".to_string(),
-        "https://example.com/user/profile".to_string(),
-        "https://news.example.ca/sport/hockey".to_string(),
+#[test]
+fn subdomain_part_is_indexed_without_registrable_domain() {
+    let rules = vec![
+        rule("api", 2, "api", vec![cond(UrlPart::Subdomain, Operator::Equals, "api")]),
+        rule("apex", 1, "apex", vec![cond(UrlPart::Subdomain, Operator::IsEmpty, "")]),
+        rule("www", 0, "www", vec![cond(UrlPart::Subdomain, Operator::StartsWith, "WWW")]),
     ];
+    let engine = RuleEngine::new(rules);
 
-    let results = processor.process_lines(&lines);
+    assert_eq!(Some("api"), engine.evaluate(&url("api.example.com", "/", "")));
+    assert_eq!(Some("apex"), engine.evaluate(&url("example.com", "/", "")));
+    assert_eq!(Some("apex"), engine.evaluate(&url("co.uk", "/", "")));
+    assert_eq!(Some("www"), engine.evaluate(&url("www.shop.example.co.uk", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("api.v2.example.com", "/", "")));
+}
 
-    assert_eq!(5, results.len());
-    assert_eq!("Canada Sport", results[0].result);
-    assert_eq!("Example Home", results[1].result);
-    // /admin/panel: Example Home requires path=/, so doesn't match.
-    // Not Admin is negated starts_with /admin → fails.
-    assert_eq!("NO_MATCH", results[2].result);
-    assert_eq!("Not Admin", results[3].result);
-    assert_eq!("Canada Sport", results[4].result);
+#[test]
+fn in_list_operator_matches_any_member() {
+    let r = rule(
+        "list",
+        1,
+        "listed",
+        vec![cond(UrlPart::Host, Operator::InList, "a.com,b.com,c.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(Some("listed"), engine.evaluate(&url("b.com", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("d.com", "/", "")));
+}
+
+#[test]
+fn in_list_counts_as_single_condition() {
+    let r = rule(
+        "list-and-path",
+        1,
+        "matched",
+        vec![
+            cond(UrlPart::Host, Operator::InList, "a.com,b.com,c.com"),
+            cond(UrlPart::Path, Operator::StartsWith, "/api"),
+        ],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(Some("matched"), engine.evaluate(&url("b.com", "/api/v1", "")));
+    assert_eq!(None, engine.evaluate(&url("b.com", "/web", "")));
+}
+
+#[test]
+fn negated_in_list() {
+    let r = rule(
+        "not-listed",
+        1,
+        "other",
+        vec![neg_cond(UrlPart::Host, Operator::InList, "a.com, b.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(None, engine.evaluate(&url("b.com", "/", "")));
+    assert_eq!(Some("other"), engine.evaluate(&url("z.com", "/", "")));
+}
+
+#[test]
+fn evaluate_templated_substitutes_url_parts() {
+    let r = rule(
+        "redirect",
+        1,
+        "redirect:https://{host}{path}?{query}",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("redirect:https://example.com/a/b?q=1".to_string()),
+        engine.evaluate_templated(&url("example.com", "/a/b", "q=1"))
+    );
+    assert_eq!(
+        Some("redirect:https://{host}{path}?{query}"),
+        engine.evaluate(&url("example.com", "/a/b", "q=1"))
+    );
+    assert_eq!(None, engine.evaluate_templated(&url("example.org", "/", "")));
+}
+
+#[test]
+fn evaluate_templated_leaves_unknown_tokens_literal() {
+    let r = rule(
+        "t",
+        1,
+        "{file}-{unknown}-{host",
+        vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("index.html-{unknown}-{host".to_string()),
+        engine.evaluate_templated(&url("x.com", "/index.html", ""))
+    );
+}
+
+#[test]
+fn evaluate_templated_substitutes_glob_captures() {
+    let r = rule(
+        "user",
+        1,
+        "user:$1 tab:$2 cost:$5",
+        vec![
+            cond(UrlPart::Host, Operator::Equals, "x.com"),
+            neg_cond(UrlPart::Path, Operator::Glob, "/admin/*"),
+            cond(UrlPart::Path, Operator::Glob, "/user/*/?*"),
+        ],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("user:12345 tab:p cost:$5".to_string()),
+        engine.evaluate_templated(&url("x.com", "/user/12345/profile", ""))
+    );
+}
+
+#[test]
+fn evaluate_templated_without_glob_leaves_dollar_literal() {
+    let r = rule(
+        "price",
+        1,
+        "$1 off",
+        vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("$1 off".to_string()),
+        engine.evaluate_templated(&url("x.com", "/", ""))
+    );
+}
+
+#[test]
+fn contains_at_least_counts_occurrences() {
+    let r = rule(
+        "tracking",
+        1,
+        "tracking-heavy",
+        vec![cond(UrlPart::Query, Operator::ContainsAtLeast, "utm_").with_min_count(2)],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(
+        Some("tracking-heavy"),
+        engine.evaluate(&url("x.com", "/", "utm_source=a&utm_medium=b"))
+    );
+    assert_eq!(None, engine.evaluate(&url("x.com", "/", "utm_source=a")));
+}
+
+#[test]
+fn contains_at_least_counts_non_overlapping() {
+    let r = rule(
+        "aaa",
+        1,
+        "matched",
+        vec![cond(UrlPart::Path, Operator::ContainsAtLeast, "aa").with_min_count(2)],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(None, engine.evaluate(&url("x.com", "/aaa", "")));
+    assert_eq!(Some("matched"), engine.evaluate(&url("x.com", "/aaaa", "")));
+}
+
+#[test]
+fn host_suffix_operator_respects_label_boundaries() {
+    let r = rule(
+        "example",
+        1,
+        "example",
+        vec![cond(UrlPart::Host, Operator::HostSuffix, "example.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(Some("example"), engine.evaluate(&url("a.example.com", "/", "")));
+    assert_eq!(Some("example"), engine.evaluate(&url("example.com", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("notexample.com", "/", "")));
+}
+
+#[test]
+fn negated_host_suffix() {
+    let r = rule(
+        "not-example",
+        1,
+        "other",
+        vec![neg_cond(UrlPart::Host, Operator::HostSuffix, ".example.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(None, engine.evaluate(&url("a.example.com", "/", "")));
+    assert_eq!(Some("other"), engine.evaluate(&url("notexample.com", "/", "")));
+}
+
+#[test]
+fn conditionless_rule_is_a_catch_all() {
+    let catch_all = rule("fallback", 1, "other", vec![]);
+    let specific = rule(
+        "sport",
+        10,
+        "sport",
+        vec![cond(UrlPart::Path, Operator::Contains, "sport")],
+    );
+    let engine = RuleEngine::new(vec![catch_all, specific]);
+
+    assert_eq!(Some("sport"), engine.evaluate(&url("news.ca", "/sport/hockey", "")));
+    assert_eq!(Some("other"), engine.evaluate(&url("news.ca", "/weather", "")));
+    assert_eq!(Some("other"), engine.evaluate(&url("", "", "")));
+}
+
+#[test]
+fn evaluate_batch_preserves_order() {
+    let ca = rule(
+        "ca",
+        2,
+        "canada",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")],
+    );
+    let sport = rule(
+        "sport",
+        1,
+        "sport",
+        vec![cond(UrlPart::Path, Operator::StartsWith, "/sport")],
+    );
+    let engine = RuleEngine::new(vec![ca, sport]);
+
+    let urls: Vec<ParsedUrl> = (0..1000)
+        .map(|i| match i % 3 {
+            0 => url(&format!("site{}.ca", i), "/news", ""),
+            1 => url(&format!("site{}.com", i), "/sport/hockey", ""),
+            _ => url(&format!("site{}.com", i), "/news", ""),
+        })
+        .collect();
+    let results = engine.evaluate_batch(&urls);
+
+    assert_eq!(1000, results.len());
+    for (i, matched) in results.iter().enumerate() {
+        let expected = match i % 3 {
+            0 => Some("ca"),
+            1 => Some("sport"),
+            _ => None,
+        };
+        assert_eq!(expected, matched.map(|r| r.name.as_str()), "url #{}", i);
+    }
+}
+
+
+#[test]
+fn evaluate_parallel_agrees_with_evaluate() {
+    let rules = vec![
+        rule("ca", 3, "canada", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+        rule(
+            "sport",
+            2,
+            "sport",
+            vec![
+                cond(UrlPart::Path, Operator::StartsWith, "/sport"),
+                neg_cond(UrlPart::Query, Operator::Contains, "lang=fr"),
+            ],
+        ),
+        rule("ads", 1, "ads", vec![cond(UrlPart::Query, Operator::Contains, "utm_")]),
+        rule("home", 0, "home", vec![cond(UrlPart::Path, Operator::Equals, "/")]),
+    ];
+    let engine = RuleEngine::new(rules);
+
+    for i in 0..300 {
+        let host = if i % 2 == 0 { format!("site{i}.ca") } else { format!("site{i}.com") };
+        let path = ["/", "/sport/hockey", "/news"][i % 3];
+        let query = ["", "lang=fr", "utm_source=x", "lang=en&utm_medium=y"][i % 4];
+        let u = url(&host, path, query);
+        assert_eq!(engine.evaluate(&u), engine.evaluate_parallel(&u), "{host}{path}?{query}");
+    }
+}
+
+#[test]
+fn blocklist_is_one_rule_checked_with_one_lookup() {
+    let path = std::env::temp_dir().join(format!("rule-engine-blocklist-{}.txt", std::process::id()));
+    let mut content = String::from("# blocked hosts\n\n");
+    for i in 0..1000 {
+        content.push_str(&format!("bad{i}.example\n"));
+    }
+    content.push_str("spam.ca\n");
+    std::fs::write(&path, content).unwrap();
+    let blocklist =
+        RuleLoader::load_blocklist(&path, UrlPart::Host, "blocklist", 10, "blocked").unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(("blocklist", 10), (blocklist.name.as_str(), blocklist.priority));
+    assert_eq!(1001, blocklist.conditions[0].list_values().len());
+
+    let engine = RuleEngine::new(vec![
+        blocklist,
+        rule("ca", 1, "canada", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+    ]);
+    assert_eq!(2, engine.rule_count());
+    let (result, metrics) = engine.evaluate_instrumented(&url("bad999.example", "/", ""));
+    assert_eq!(Some("blocked"), result);
+    assert_eq!(1, metrics.candidates_touched);
+    assert_eq!(Some("blocked"), engine.evaluate(&url("bad0.example", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("good.example", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("# blocked hosts", "/", "")));
+    // The blocklist's priority beats the lower-priority `.ca` rule.
+    assert_eq!(Some("blocked"), engine.evaluate(&url("spam.ca", "/", "")));
+    assert_eq!(Some("canada"), engine.evaluate(&url("ham.ca", "/", "")));
+}
+
+#[test]
+fn all_negated_rules_are_confirmed_by_the_index() {
+    let rules = vec![
+        rule(
+            "not-ca-admin",
+            5,
+            "a",
+            vec![
+                neg_cond(UrlPart::Host, Operator::HostSuffix, "example.ca"),
+                neg_cond(UrlPart::Path, Operator::StartsWith, "/admin"),
+            ],
+        ),
+        rule(
+            "not-html-or-home",
+            4,
+            "b",
+            vec![
+                neg_cond(UrlPart::File, Operator::EndsWith, ".html"),
+                neg_cond(UrlPart::Path, Operator::Equals, "/"),
+            ],
+        ),
+        rule(
+            "not-listed",
+            3,
+            "c",
+            vec![
+                neg_cond(UrlPart::Host, Operator::InList, "a.com,b.com"),
+                neg_cond(UrlPart::Query, Operator::IsEmpty, ""),
+            ],
+        ),
+        rule(
+            "no-tracking",
+            2,
+            "d",
+            vec![Condition::new(UrlPart::Query, Operator::ContainsAny, "", true)
+                .with_values(["utm_", "fbclid"])],
+        ),
+        rule("fallback", 1, "e", vec![neg_cond(UrlPart::Path, Operator::Contains, "x")]),
+    ];
+    let plain = RuleEngine::new(rules.clone());
+    let engine = RuleEngine::new(rules.clone()).with_indexed_negations();
+
+    // The conditions above, checked the slow way.
+    let holds = |c: &Condition, u: &ParsedUrl| {
+        let v = u.part(c.part);
+        let matched = match c.operator {
+            Operator::HostSuffix => v == c.value || v.ends_with(&format!(".{}", c.value)),
+            Operator::StartsWith => v.starts_with(&c.value),
+            Operator::EndsWith => v.ends_with(&c.value),
+            Operator::Equals => v == c.value,
+            Operator::InList => c.list_values().contains(&v),
+            Operator::IsEmpty => v.is_empty(),
+            Operator::ContainsAny => c.values.iter().any(|s| v.contains(s.as_str())),
+            Operator::Contains => v.contains(&c.value),
+            _ => unreachable!(),
+        };
+        matched != c.negated
+    };
+
+    let hosts = ["example.ca", "shop.example.ca", "notexample.ca", "a.com", "c.com"];
+    let paths = ["/", "/admin/x", "/page.html", "/x/y", "/docs"];
+    let queries = ["", "utm_source=1", "q=1", "fbclid=2"];
+    for host in hosts {
+        for path in paths {
+            for query in queries {
+                let u = url(host, path, query);
+                let expected = rules
+                    .iter()
+                    .find(|r| r.conditions.iter().all(|c| holds(c, &u)))
+                    .map(|r| r.result.as_str());
+                let (result, metrics) = engine.evaluate_instrumented(&u);
+                assert_eq!(expected, result, "{host}{path}?{query}");
+                assert_eq!(expected, plain.evaluate(&u), "{host}{path}?{query}");
+                assert_eq!(0, metrics.direct_checks);
+            }
+        }
+    }
+}
+
+#[test]
+fn to_dot_lists_rules_in_priority_order() {
+    let engine = RuleEngine::new(vec![
+        rule("low", 1, "l", vec![neg_cond(UrlPart::Path, Operator::Contains, "x")]),
+        rule("high", 10, "say \"hi\"", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+        rule("mid-a", 5, "a", vec![]),
+        rule("mid-b", 5, "b", vec![cond(UrlPart::Path, Operator::StartsWith, "/b")]),
+    ]);
+    let dot = engine.to_dot();
+
+    assert!(dot.starts_with("digraph rules {"));
+    assert!(dot.contains(r#"r0 [label="high\npriority 10 -> say \"hi\"\nHost EndsWith \".ca\""];"#));
+    assert!(dot.contains(r#"not Path Contains \"x\""#));
+    let positions: Vec<usize> = ["high", "mid-a", "mid-b", "low"]
+        .iter()
+        .map(|name| dot.find(&format!("\"{name}\\n")).unwrap())
+        .collect();
+    assert!(positions.is_sorted());
+    assert_eq!(3, dot.matches("subgraph cluster_").count());
+    assert!(dot.contains("label=\"priority 5\";"));
+    assert!(dot.contains("r2 -> r3 [style=dashed];"));
+}
+
+#[test]
+fn host_conditions_ignore_value_case() {
+    let engine = RuleEngine::new(vec![
+        rule("ca", 2, "canada", vec![cond(UrlPart::Host, Operator::EndsWith, ".CA")]),
+        rule("not-shop", 1, "other", vec![neg_cond(UrlPart::Host, Operator::StartsWith, "SHOP.")]),
+        rule("pdf", 3, "pdf", vec![cond(UrlPart::Path, Operator::EndsWith, ".PDF")]),
+    ]);
+
+    let url = UrlParser::parse("https://Shop.Example.CA/doc.pdf").unwrap();
+    assert_eq!(Some("canada"), engine.evaluate(&url));
+    let url = UrlParser::parse("https://example.com/doc.pdf").unwrap();
+    assert_eq!(Some("other"), engine.evaluate(&url));
+    let url = UrlParser::parse("https://example.ca/DOC.PDF").unwrap();
+    assert_eq!(Some("pdf"), engine.evaluate(&url));
+}
+
+fn host_case_engine(host_case: HostCase) -> RuleEngine {
+    RuleEngine::new(vec![
+        rule("istanbul", 2, "tr", vec![cond(UrlPart::Host, Operator::Equals, "İstanbul.tr")]),
+        rule("ca", 1, "canada", vec![cond(UrlPart::Host, Operator::EndsWith, ".CA")]),
+    ])
+    .with_host_case(host_case)
+}
+
+#[test]
+fn unicode_host_case_folds_values_fully() {
+    let engine = host_case_engine(HostCase::Unicode);
+    let parse = |raw| UrlParser::parse_with_host_case(raw, HostCase::Unicode).unwrap();
+    assert_eq!(Some("tr"), engine.evaluate(&parse("https://İstanbul.tr/")));
+    assert_eq!(Some("canada"), engine.evaluate(&parse("https://SHOP.EXAMPLE.CA/")));
+}
+
+#[test]
+fn ascii_host_case_folds_values_ascii_only() {
+    let engine = host_case_engine(HostCase::Ascii);
+    let parse = |raw| UrlParser::parse_with_host_case(raw, HostCase::Ascii).unwrap();
+    assert_eq!(Some("tr"), engine.evaluate(&parse("https://İSTANBUL.TR/")));
+    assert_eq!(None, engine.evaluate(&parse("https://i̇stanbul.tr/")));
+    assert_eq!(Some("canada"), engine.evaluate(&parse("https://SHOP.EXAMPLE.CA/")));
+}
+
+#[test]
+fn preserve_host_case_keeps_values_as_written() {
+    let engine = host_case_engine(HostCase::Preserve);
+    let parse = |raw| UrlParser::parse_with_host_case(raw, HostCase::Preserve).unwrap();
+    assert_eq!(Some("tr"), engine.evaluate(&parse("https://İstanbul.tr/")));
+    assert_eq!(Some("canada"), engine.evaluate(&parse("https://SHOP.EXAMPLE.CA/")));
+    assert_eq!(None, engine.evaluate(&parse("https://shop.example.ca/")));
+}
+
+#[test]
+fn strip_www_host_equals_ignores_leading_www() {
+    let json = r#"[
+      {"name":"example","priority":2,"result":"example","conditions":[
+        {"part":"host","operator":"equals","value":"example.com","strip_www":true}]},
+      {"name":"not-shop","priority":1,"result":"other","conditions":[
+        {"part":"host","operator":"equals","value":"shop.ca","strip_www":true,"negated":true}]}
+    ]"#;
+    let engine = RuleEngine::new(RuleLoader::load_from_str(json).unwrap());
+
+    for (url, expected) in [
+        ("https://www.example.com/", Some("example")),
+        ("https://example.com/", Some("example")),
+        ("https://www.www.example.com/", Some("other")),
+        ("https://wwwexample.com/", Some("other")),
+        ("https://www.shop.ca/", None),
+        ("https://shop.ca/", None),
+    ] {
+        let parsed = UrlParser::parse(url).unwrap();
+        assert_eq!(expected, engine.evaluate(&parsed), "{url}");
+    }
+}
+
+#[test]
+fn shared_engine_swaps_rules_under_concurrent_readers() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let versioned = |version: &str| {
+        RuleEngine::new(vec![rule(
+            "ca",
+            1,
+            version,
+            vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")],
+        )])
+    };
+    let shared = SharedEngine::new(versioned("v1"));
+    let url = UrlParser::parse("https://shop.example.ca/").unwrap();
+    let stop = AtomicBool::new(false);
+
+    std::thread::scope(|s| {
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut seen_new = false;
+                    while !stop.load(Ordering::Relaxed) {
+                        match shared.load().evaluate(&url) {
+                            Some("v2") => seen_new = true,
+                            Some("v1") => assert!(!seen_new, "old rules after the swap"),
+                            other => panic!("unexpected result {:?}", other),
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        std::thread::sleep(Duration::from_millis(20));
+        shared.store(versioned("v2"));
+        assert_eq!(Some("v2"), shared.load().evaluate(&url));
+        std::thread::sleep(Duration::from_millis(20));
+        stop.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    });
+}
+
+#[test]
+fn possible_results_dedup_in_priority_order() {
+    let mut engine = RuleEngine::new(vec![
+        rule("a", 1, "allow", vec![]),
+        rule("b", 5, "block", vec![]),
+        rule("c", 3, "allow", vec![]),
+        rule("d", 9, "review", vec![]),
+        rule("e", 4, "block", vec![]),
+        rule("f", 7, "quarantine", vec![]),
+    ]);
+    assert_eq!(vec!["review", "quarantine", "block", "allow"], engine.possible_results());
+
+    engine.set_enabled("f", false);
+    assert_eq!(vec!["review", "block", "allow"], engine.possible_results());
+}
+
+#[test]
+fn canonical_parse_catches_encoded_fullwidth_keyword() {
+    let engine = RuleEngine::new(vec![rule(
+        "sport-news",
+        1,
+        "sport",
+        vec![cond(UrlPart::Path, Operator::Contains, "sport news")],
+    )]);
+    let raw = "https://example.com/ｓｐｏｒｔ%20news/today";
+
+    for parse in [
+        UrlParser::parse,
+        UrlParser::parse_with_decoded,
+        UrlParser::parse_normalized_nfkc,
+    ] {
+        assert_eq!(None, engine.evaluate(&parse(raw).unwrap()));
+    }
+    assert_eq!(Some("sport"), engine.evaluate(&UrlParser::parse_canonical(raw).unwrap()));
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    events: std::sync::Mutex<Vec<(String, Option<String>)>>,
+}
+
+impl MatchObserver for RecordingObserver {
+    fn on_match(&self, rule: &Rule, url: &ParsedUrl) {
+        self.events.lock().unwrap().push((url.host.clone(), Some(rule.name.clone())));
+    }
+
+    fn on_no_match(&self, url: &ParsedUrl) {
+        self.events.lock().unwrap().push((url.host.clone(), None));
+    }
+}
+
+#[test]
+fn evaluate_observed_reports_each_outcome() {
+    use rayon::prelude::*;
+
+    let engine = RuleEngine::new(vec![
+        rule("ca", 2, "canada", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+        rule("shop", 1, "shop", vec![cond(UrlPart::Host, Operator::StartsWith, "shop.")]),
+    ]);
+    let urls: Vec<ParsedUrl> = ["https://shop.example.ca/", "https://shop.com/", "https://x.com/"]
+        .iter()
+        .map(|u| UrlParser::parse(u).unwrap())
+        .collect();
+    let observer = RecordingObserver::default();
+
+    let results: Vec<Option<&str>> =
+        urls.par_iter().map(|url| engine.evaluate_observed(url, &observer)).collect();
+
+    assert_eq!(vec![Some("canada"), Some("shop"), None], results);
+    let mut events = observer.events.into_inner().unwrap();
+    events.sort();
+    assert_eq!(
+        vec![
+            ("shop.com".to_string(), Some("shop".to_string())),
+            ("shop.example.ca".to_string(), Some("ca".to_string())),
+            ("x.com".to_string(), None),
+        ],
+        events
+    );
+}
+
+#[test]
+fn evaluate_instrumented_counts_work() {
+    let engine = RuleEngine::new(vec![rule(
+        "ca",
+        1,
+        "canada",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")],
+    )]);
+    let (result, metrics) = engine.evaluate_instrumented(&url("news.ca", "/", ""));
+    assert_eq!(Some("canada"), result);
+    assert_eq!(1, metrics.rules_verified);
+    assert_eq!(1, metrics.candidates_touched);
+    assert_eq!(0, metrics.negated_checks);
+
+    let engine = RuleEngine::new(vec![rule(
+        "public",
+        1,
+        "public",
+        vec![
+            cond(UrlPart::Host, Operator::EndsWith, ".ca"),
+            neg_cond(UrlPart::Path, Operator::StartsWith, "/admin"),
+        ],
+    )]);
+    let (result, metrics) = engine.evaluate_instrumented(&url("news.ca", "/admin", ""));
+    assert_eq!(None, result);
+    assert_eq!(1, metrics.rules_verified);
+    assert_eq!(1, metrics.negated_checks);
+    assert_eq!(engine.evaluate(&url("news.ca", "/", "")), Some("public"));
+}
+
+#[test]
+fn contains_any_matches_any_value() {
+    let topics = Condition::new(UrlPart::Path, Operator::ContainsAny, "", false)
+        .with_values(["sport", "news", "finance"]);
+    let r = rule(
+        "topics",
+        1,
+        "topic",
+        vec![topics, cond(UrlPart::Host, Operator::EndsWith, ".ca")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(Some("topic"), engine.evaluate(&url("cbc.ca", "/news/today", "")));
+    assert_eq!(Some("topic"), engine.evaluate(&url("cbc.ca", "/news/sport", "")));
+    assert_eq!(None, engine.evaluate(&url("cbc.ca", "/weather", "")));
+}
+
+#[test]
+fn repeated_contains_occurrence_still_matches() {
+    let r = rule(
+        "a",
+        1,
+        "hit",
+        vec![cond(UrlPart::Path, Operator::Contains, "an")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(Some("hit"), engine.evaluate(&url("x.com", "/banana", "")));
+}
+
+#[test]
+fn matches_any_short_circuits_on_single_match() {
+    let mut rules: Vec<Rule> = (0..200)
+        .map(|i| {
+            rule(
+                &format!("host-{}", i),
+                i,
+                "blocked",
+                vec![cond(UrlPart::Host, Operator::Equals, &format!("site{}.com", i))],
+            )
+        })
+        .collect();
+    rules.push(rule(
+        "not-admin",
+        1,
+        "open",
+        vec![
+            cond(UrlPart::Host, Operator::EndsWith, ".org"),
+            neg_cond(UrlPart::Path, Operator::StartsWith, "/admin"),
+        ],
+    ));
+    let engine = RuleEngine::new(rules);
+
+    assert!(engine.matches_any(&url("site42.com", "/", "")));
+    assert!(engine.matches_any(&url("wiki.org", "/home", "")));
+    assert!(!engine.matches_any(&url("wiki.org", "/admin", "")));
+    assert!(!engine.matches_any(&url("other.net", "/", "")));
+}
+
+#[test]
+fn rule_by_id_survives_reordering() {
+    let sport = rule(
+        "sport",
+        1,
+        "sport",
+        vec![cond(UrlPart::Path, Operator::Contains, "sport")],
+    )
+    .with_id("rule-sport");
+    let news = rule(
+        "news",
+        2,
+        "news",
+        vec![cond(UrlPart::Path, Operator::Contains, "news")],
+    )
+    .with_id("rule-news");
+
+    let engine = RuleEngine::new(vec![sport.clone(), news.clone()]);
+    let reordered = RuleEngine::new(vec![news, sport]);
+    for e in [&engine, &reordered] {
+        assert_eq!("sport", e.rule_by_id("rule-sport").unwrap().name);
+        assert_eq!("news", e.rule_by_id("rule-news").unwrap().name);
+        assert!(e.rule_by_id("missing").is_none());
+    }
+}
+
+#[test]
+fn warmup_preallocates_thread_local_buffers() {
+    let rules: Vec<Rule> = (0..50)
+        .map(|i| {
+            rule(
+                &format!("r{}", i),
+                1,
+                "hit",
+                vec![cond(UrlPart::Host, Operator::EndsWith, &format!(".tld{}", i))],
+            )
+        })
+        .collect();
+    let engine = RuleEngine::new(rules);
+
+    // A fresh thread starts with empty buffers.
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            assert_eq!(0, RuleEngine::thread_local_capacity());
+            engine.warmup_thread_local();
+            assert_eq!(50, RuleEngine::thread_local_capacity());
+            assert_eq!(Some("hit"), engine.evaluate(&url("a.tld7", "/", "")));
+            assert_eq!(50, RuleEngine::thread_local_capacity());
+        });
+    });
+}
+
+#[test]
+fn owned_context_matches_thread_local_path() {
+    let engine = RuleEngine::new(vec![
+        rule(
+            "ca-sport",
+            2,
+            "canada-sport",
+            vec![
+                cond(UrlPart::Host, Operator::EndsWith, ".ca"),
+                cond(UrlPart::Path, Operator::Contains, "sport"),
+            ],
+        ),
+        rule(
+            "not-admin",
+            1,
+            "public",
+            vec![neg_cond(UrlPart::Path, Operator::StartsWith, "/admin")],
+        ),
+    ]);
+    let mut ctx = engine.new_context();
+
+    for i in 0..10_000 {
+        let host = if i % 2 == 0 { "news.ca" } else { "news.com" };
+        let path = match i % 3 {
+            0 => "/sport/hockey",
+            1 => "/admin",
+            _ => "/weather",
+        };
+        let u = url(host, path, "");
+        assert_eq!(engine.evaluate(&u), engine.evaluate_with(&mut ctx, &u));
+    }
+}
+
+#[test]
+fn matches_operator_checks_both_bounds() {
+    let api_json = Condition::new(UrlPart::Path, Operator::Matches, "", false)
+        .with_bounds("/api", ".json");
+    let engine = RuleEngine::new(vec![rule("api-json", 1, "json", vec![api_json])]);
+
+    assert_eq!(Some("json"), engine.evaluate(&url("x.com", "/api/x/y.json", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/api/x/y.xml", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/web/y.json", "")));
+}
+
+#[test]
+fn matches_operator_bounds_do_not_overlap() {
+    let bounded = Condition::new(UrlPart::Path, Operator::Matches, "", false)
+        .with_bounds("/ab", "bc");
+    let engine = RuleEngine::new(vec![rule("bounded", 1, "hit", vec![bounded])]);
+
+    assert_eq!(None, engine.evaluate(&url("x.com", "/abc", "")));
+    assert_eq!(Some("hit"), engine.evaluate(&url("x.com", "/abbc", "")));
+}
+
+#[test]
+fn broad_high_priority_rule_shadows_specific_one() {
+    let broad = rule(
+        "dot-com",
+        10,
+        "com",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".com")],
+    );
+    let specific = rule(
+        "example",
+        1,
+        "example",
+        vec![cond(UrlPart::Host, Operator::Equals, "example.com")],
+    );
+    let unrelated = rule(
+        "example-org",
+        1,
+        "org",
+        vec![cond(UrlPart::Host, Operator::Equals, "example.org")],
+    );
+    let engine = RuleEngine::new(vec![specific.clone(), broad.clone(), unrelated]);
+
+    assert_eq!(
+        vec![("example".to_string(), "dot-com".to_string())],
+        engine.find_shadowed_rules()
+    );
+
+    // Reversing the priorities lets the specific rule fire first.
+    let mut specific = specific;
+    specific.priority = 20;
+    assert!(RuleEngine::new(vec![specific, broad]).find_shadowed_rules().is_empty());
+}
+
+#[test]
+fn is_empty_operator() {
+    let no_query = rule(
+        "no-query",
+        1,
+        "clean",
+        vec![cond(UrlPart::Query, Operator::IsEmpty, "")],
+    );
+    let engine = RuleEngine::new(vec![no_query]);
+
+    let parse = |raw: &str| UrlParser::parse(raw).unwrap();
+    assert_eq!(Some("clean"), engine.evaluate(&parse("https://x.com/")));
+    assert_eq!(None, engine.evaluate(&parse("https://x.com/?a=1")));
+}
+
+#[test]
+fn negated_is_empty_means_present() {
+    let has_file = rule(
+        "has-file",
+        1,
+        "file",
+        vec![neg_cond(UrlPart::File, Operator::IsEmpty, "")],
+    );
+    let engine = RuleEngine::new(vec![has_file]);
+
+    assert_eq!(Some("file"), engine.evaluate(&url("x.com", "/a/index.html", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/a/", "")));
+}
+
+#[test]
+fn evaluate_all_returns_every_match_in_priority_order() {
+    let engine = RuleEngine::new(vec![
+        rule("low", 1, "low", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+        rule("miss", 9, "miss", vec![cond(UrlPart::Host, Operator::EndsWith, ".fr")]),
+        rule("high", 5, "high", vec![cond(UrlPart::Path, Operator::Contains, "sport")]),
+        rule("catch-all", 0, "any", vec![]),
+    ]);
+
+    let names: Vec<&str> = engine
+        .evaluate_all(&url("news.ca", "/sport", ""))
+        .iter()
+        .map(|r| r.name.as_str())
+        .collect();
+    assert_eq!(vec!["high", "low", "catch-all"], names);
+}
+
+#[test]
+fn score_sums_priorities_per_result() {
+    let engine = RuleEngine::new(vec![
+        rule("ca", 3, "news", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+        rule("sport", 5, "sport", vec![cond(UrlPart::Path, Operator::Contains, "sport")]),
+        rule("news", 4, "news", vec![cond(UrlPart::Path, Operator::Contains, "news")]),
+        rule("miss", 100, "miss", vec![cond(UrlPart::Query, Operator::Contains, "x")]),
+    ]);
+
+    assert_eq!(
+        vec![("news", 7), ("sport", 5)],
+        engine.score(&url("cbc.ca", "/news/sport", ""))
+    );
+    assert!(engine.score(&url("cbc.com", "/", "")).is_empty());
+}
+
+#[test]
+fn rules_accessor_keeps_definition_order() {
+    let mut disabled = rule("disabled", 5, "d", vec![]);
+    disabled.enabled = false;
+    let input = vec![
+        rule("low", 1, "low", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+        rule("high", 10, "high", vec![cond(UrlPart::Host, Operator::EndsWith, ".com")]),
+        disabled,
+    ];
+    let engine = RuleEngine::new(input.clone());
+
+    assert_eq!(input.len(), engine.rule_count());
+    assert_eq!(input.as_slice(), engine.rules());
+}
+
+#[test]
+fn nfkc_parsing_defeats_fullwidth_evasion() {
+    let r = rule(
+        "sport",
+        1,
+        "sport",
+        vec![cond(UrlPart::Path, Operator::Contains, "sport")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+    let raw = "https://example.com/\u{FF53}\u{FF50}\u{FF4F}\u{FF52}\u{FF54}/live";
+
+    assert_eq!(None, engine.evaluate(&UrlParser::parse(raw).unwrap()));
+    assert_eq!(
+        Some("sport"),
+        engine.evaluate(&UrlParser::parse_normalized_nfkc(raw).unwrap())
+    );
+}
+
+#[test]
+fn extension_in_operator() {
+    let images = rule(
+        "images",
+        1,
+        "image",
+        vec![cond(UrlPart::File, Operator::ExtensionIn, "png, jpg,.gif")],
+    );
+    let engine = RuleEngine::new(vec![images]);
+
+    assert_eq!(Some("image"), engine.evaluate(&url("x.com", "/img/photo.PNG", "")));
+    assert_eq!(Some("image"), engine.evaluate(&url("x.com", "/a.b/anim.gif", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/docs/README", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/repo/.png", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/photo.png.txt", "")));
+}
+
+#[test]
+fn full_part_matches_anywhere_in_the_url() {
+    let tracking = rule(
+        "tracking",
+        1,
+        "tracked",
+        vec![cond(UrlPart::Full, Operator::Contains, "utm_")],
+    );
+    let engine = RuleEngine::new(vec![tracking]);
+    let parse = |raw: &str| UrlParser::parse_keep_raw(raw).unwrap();
+
+    assert_eq!(Some("tracked"), engine.evaluate(&parse("https://x.com/?utm_source=a")));
+    assert_eq!(Some("tracked"), engine.evaluate(&parse("https://x.com/utm_/page")));
+    assert_eq!(Some("tracked"), engine.evaluate(&parse("https://utm_.x.com/")));
+    assert_eq!(None, engine.evaluate(&parse("https://x.com/?ref=a")));
+    assert_eq!(
+        None,
+        engine.evaluate(&UrlParser::parse("https://x.com/?utm_source=a").unwrap())
+    );
+}
+
+#[test]
+fn decoded_conditions_match_percent_decoded_parts() {
+    let decoded = rule(
+        "decoded",
+        1,
+        "decoded",
+        vec![cond(UrlPart::Path, Operator::Contains, "hello world").with_decoded(true)],
+    );
+    let raw = rule(
+        "raw",
+        2,
+        "raw",
+        vec![cond(UrlPart::Path, Operator::Contains, "hello world")],
+    );
+    let glob = rule(
+        "glob",
+        0,
+        "glob",
+        vec![cond(UrlPart::File, Operator::Glob, "* world").with_decoded(true)],
+    );
+    let engine = RuleEngine::new(vec![decoded, raw, glob]);
+    let url = UrlParser::parse_with_decoded("https://x.com/say/hello%20world").unwrap();
+
+    let names: Vec<&str> = engine.evaluate_all(&url).iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(vec!["decoded", "glob"], names);
+    assert_eq!(
+        Some("raw"),
+        engine.evaluate(&UrlParser::parse_with_decoded("https://x.com/hello world").unwrap())
+    );
+    // Without decoded parts, decoded conditions see the raw value.
+    assert_eq!(
+        None,
+        engine.evaluate(&UrlParser::parse("https://x.com/hello%20world").unwrap())
+    );
+}
+
+#[test]
+fn segment_equals_targets_nth_path_segment() {
+    let segment = |n: usize, value: &str| {
+        let c = cond(UrlPart::Path, Operator::SegmentEquals, value).with_segment(n);
+        RuleEngine::new(vec![rule("seg", 1, "hit", vec![c])])
+    };
+    let u = url("api.com", "/api/v2/users", "");
+
+    assert_eq!(Some("hit"), segment(0, "api").evaluate(&u));
+    assert_eq!(Some("hit"), segment(1, "v2").evaluate(&u));
+    assert_eq!(None, segment(1, "api").evaluate(&u));
+    assert_eq!(None, segment(5, "").evaluate(&u));
+    assert_eq!(Some("hit"), segment(1, "v2").evaluate(&url("api.com", "//api//v2", "")));
+}
+
+#[test]
+fn has_segment_matches_whole_path_segments_only() {
+    let has = |value: &str| {
+        let c = cond(UrlPart::Path, Operator::HasSegment, value);
+        RuleEngine::new(vec![rule("seg", 1, "hit", vec![c])])
+    };
+    let admin = has("admin");
+
+    assert_eq!(Some("hit"), admin.evaluate(&url("x.com", "/api/admin/users", "")));
+    assert_eq!(None, admin.evaluate(&url("x.com", "/administrator/x", "")));
+    assert_eq!(None, admin.evaluate(&url("x.com", "/my-admin", "")));
+    assert_eq!(Some("hit"), admin.evaluate(&url("x.com", "admin", "")));
+    assert_eq!(Some("hit"), admin.evaluate(&url("x.com", "//x//admin/", "")));
+    assert_eq!(None, has("").evaluate(&url("x.com", "//x//", "")));
+}
+
+#[test]
+fn num_in_range_reads_first_integer() {
+    let engine = RuleEngine::new(vec![
+        rule(
+            "v2-5",
+            2,
+            "supported",
+            vec![cond(UrlPart::Path, Operator::NumInRange, "").with_range(2, 5)],
+        ),
+        rule(
+            "page",
+            1,
+            "early-page",
+            vec![cond(UrlPart::Query, Operator::NumInRange, "").with_range(0, 3).with_param("page")],
+        ),
+    ]);
+
+    assert_eq!(Some("supported"), engine.evaluate(&url("x.com", "/api/v3/users/42", "")));
+    assert_eq!(Some("supported"), engine.evaluate(&url("x.com", "/api/v5", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/api/v9/users", "")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/api/users", "")));
+    assert_eq!(Some("early-page"), engine.evaluate(&url("x.com", "/", "id=99&page=2")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/", "page=last")));
+
+    let json = r#"[{"name":"r","priority":1,"result":"x","conditions":[
+      {"part":"path","operator":"num_in_range","min":2}]}]"#;
+    let open_ended = RuleEngine::new(RuleLoader::load_from_str(json).unwrap());
+    assert_eq!(Some("x"), open_ended.evaluate(&url("x.com", "/v900", "")));
+}
+
+#[test]
+fn for_each_match_visits_in_priority_order_and_can_stop() {
+    let engine = RuleEngine::new(vec![
+        rule("low", 1, "l", vec![cond(UrlPart::Host, Operator::EndsWith, ".com")]),
+        rule("high", 9, "h", vec![cond(UrlPart::Path, Operator::StartsWith, "/a")]),
+        rule("mid", 5, "m", vec![]),
+        rule("miss", 7, "x", vec![cond(UrlPart::Host, Operator::Equals, "other.com")]),
+    ]);
+    let u = url("x.com", "/a", "");
+
+    let mut seen = Vec::new();
+    let flow = engine.for_each_match(&u, &mut |r| {
+        seen.push(r.name.as_str());
+        ControlFlow::Continue(())
+    });
+    assert_eq!(ControlFlow::Continue(()), flow);
+    assert_eq!(vec!["high", "mid", "low"], seen);
+
+    let mut calls = 0;
+    let flow = engine.for_each_match(&u, &mut |_| {
+        calls += 1;
+        if calls == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    });
+    assert_eq!(ControlFlow::Break(()), flow);
+    assert_eq!(2, calls);
+}
+
+#[test]
+fn host_pattern_wildcard_matches_single_label() {
+    let r = rule(
+        "staging",
+        1,
+        "api",
+        vec![cond(UrlPart::Host, Operator::HostPattern, "api.*.example.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    assert_eq!(Some("api"), engine.evaluate(&url("api.staging.example.com", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("api.a.b.example.com", "/", "")));
+    assert_eq!(None, engine.evaluate(&url("api.example.com", "/", "")));
+}
+
+#[test]
+fn negated_contains_index_agrees_with_direct_evaluation() {
+    // Reference: every condition checked directly against its part.
+    fn holds(c: &Condition, u: &ParsedUrl) -> bool {
+        let value = u.part(c.part);
+        let matched = match c.operator {
+            Operator::Contains => value.contains(c.value.as_str()),
+            Operator::StartsWith => value.starts_with(c.value.as_str()),
+            Operator::Equals => value == c.value,
+            other => unreachable!("not used here: {:?}", other),
+        };
+        matched != c.negated
+    }
+
+    let rules = vec![
+        rule("no-tmp", 9, "a", vec![neg_cond(UrlPart::Path, Operator::Contains, "tmp")]),
+        rule("api-no-debug", 8, "b", vec![
+            cond(UrlPart::Path, Operator::StartsWith, "/api"),
+            neg_cond(UrlPart::Query, Operator::Contains, "debug"),
+        ]),
+        rule("two-negs", 7, "c", vec![
+            neg_cond(UrlPart::Host, Operator::Contains, "test"),
+            neg_cond(UrlPart::Path, Operator::Contains, "a"),
+        ]),
+        rule("neg-and-pos-same", 6, "d", vec![
+            cond(UrlPart::Path, Operator::Contains, "x"),
+            neg_cond(UrlPart::Path, Operator::Contains, "xx"),
+        ]),
+        rule("host", 5, "e", vec![cond(UrlPart::Host, Operator::Equals, "test.com")]),
+    ];
+    let engine = RuleEngine::new(rules.clone());
+
+    for host in ["test.com", "prod.com"] {
+        for path in ["/", "/api/x", "/tmp/xx", "/api/tmp", "/b/x", "/xx"] {
+            for query in ["", "debug=1", "q=1"] {
+                let u = url(host, path, query);
+                let expected: Vec<&str> = engine
+                    .rules()
+                    .iter()
+                    .filter(|r| r.conditions.iter().all(|c| holds(c, &u)))
+                    .map(|r| r.name.as_str())
+                    .collect();
+                let mut actual: Vec<&str> =
+                    engine.evaluate_all(&u).iter().map(|r| r.name.as_str()).collect();
+                actual.sort_by_key(|name| rules.iter().position(|r| r.name == *name));
+                assert_eq!(expected, actual, "{} {} {}", host, path, query);
+                assert_eq!(!expected.is_empty(), engine.matches_any(&u));
+            }
+        }
+    }
+}
+
+#[test]
+fn canonical_query_ignores_param_order() {
+    let rules = vec![rule(
+        "exact",
+        1,
+        "q",
+        vec![cond(UrlPart::Query, Operator::Equals, "a=1&b=2")],
+    )];
+    let canonical = RuleEngine::new_with_canonical_query(rules.clone());
+    let default = RuleEngine::new(rules);
+    let swapped = url("x.com", "/", "b=2&a=1");
+
+    assert_eq!(Some("q"), canonical.evaluate(&swapped));
+    assert_eq!(None, default.evaluate(&swapped));
+    assert_eq!(Some("q"), canonical.evaluate(&url("x.com", "/", "a=1&&b=2")));
+    assert_eq!(None, canonical.evaluate(&url("x.com", "/", "b=2&a=1&a=1")));
+    assert!(canonical.matches_any(&swapped));
+}
+
+#[test]
+fn evaluate_parts_matches_query_pairs() {
+    let engine = RuleEngine::new(vec![
+        rule("lang", 2, "en", vec![cond(UrlPart::Query, Operator::Contains, "lang=en")]),
+        rule("flag", 1, "flag", vec![cond(UrlPart::Query, Operator::Equals, "debug")]),
+    ]);
+
+    assert_eq!(
+        Some("en"),
+        engine.evaluate_parts("x.com", "/a/b", "b", &[("page", "2"), ("lang", "en")])
+    );
+    assert_eq!(Some("en"), engine.evaluate_parts("x.com", "/", "", &[("lang", "en")]));
+    assert_eq!(Some("flag"), engine.evaluate_parts("x.com", "/", "", &[("debug", "")]));
+    assert_eq!(None, engine.evaluate_parts("x.com", "/", "", &[("lang", "fr")]));
+    assert_eq!(None, engine.evaluate_parts("x.com", "/", "", &[]));
+    // Delimiters inside a value never make up a pair.
+    assert_eq!(None, engine.evaluate_parts("x.com", "/", "", &[("q", "a&lang=en")]));
+
+    let params = RuleEngine::new(vec![
+        rule("lang", 3, "en", vec![
+            cond(UrlPart::Query, Operator::Equals, "en").with_param("lang"),
+        ]),
+        rule("q", 2, "q", vec![
+            cond(UrlPart::Query, Operator::Equals, "a&b=c").with_param("q"),
+        ]),
+        rule("no-token", 1, "open", vec![
+            cond(UrlPart::Query, Operator::ParamAbsent, "token"),
+        ]),
+    ]);
+    let eval = |pairs: &[(&str, &str)]| params.evaluate_parts("x.com", "/", "", pairs);
+    assert_eq!(Some("en"), eval(&[("page", "2"), ("lang", "en")]));
+    assert_eq!(Some("q"), eval(&[("q", "a&b=c"), ("token", "t")]));
+    assert_eq!(Some("open"), eval(&[("q", "x&lang=en&token=t")]));
+    assert_eq!(None, eval(&[("q", "x"), ("token", "")]));
+    // A key holding `=` is kept whole rather than split again.
+    assert_eq!(Some("en"), eval(&[("token=1", ""), ("lang", "en")]));
+    assert_eq!(Some("open"), eval(&[("token=1", "")]));
+}
+
+#[test]
+fn equals_part_compares_two_parts_of_the_url() {
+    let engine = RuleEngine::new(vec![
+        rule("echo", 2, "echo", vec![cond(UrlPart::File, Operator::EqualsPart, "query")]),
+        rule("ssrf", 1, "ssrf", vec![cond(UrlPart::Host, Operator::EqualsPart, "query")]),
+    ]);
+
+    assert_eq!(Some("echo"), engine.evaluate(&url("x.com", "/dl/report.pdf", "report.pdf")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/dl/report.pdf", "other.pdf")));
+    assert_eq!(Some("ssrf"), engine.evaluate(&url("10.0.0.1", "/fetch", "10.0.0.1")));
+
+    let json = r#"[{"name":"bad","priority":1,"result":"x",
+      "conditions":[{"part":"file","operator":"equals_part","value":"segment"}]}]"#;
+    let err = RuleLoader::load_from_str(json).unwrap_err();
+    assert!(matches!(err, RuleEngineError::Validation(_)));
+}
+
+#[test]
+fn param_absent_matches_only_without_the_key() {
+    let absent = |key| RuleEngine::new(vec![rule("no-key", 1, "absent", vec![
+        cond(UrlPart::Query, Operator::ParamAbsent, key),
+    ])]);
+
+    assert_eq!(Some("absent"), absent("c").evaluate(&url("x.com", "/", "a=1&b=2")));
+    assert_eq!(None, absent("a").evaluate(&url("x.com", "/", "a=1&b=2")));
+    assert_eq!(None, absent("b").evaluate(&url("x.com", "/", "a=1&b")));
+    assert_eq!(Some("absent"), absent("a").evaluate(&url("x.com", "/", "ab=1&b=a")));
+    assert_eq!(Some("absent"), absent("token").evaluate(&url("x.com", "/", "")));
+
+    let present = RuleEngine::new(vec![rule("has-token", 1, "present", vec![
+        neg_cond(UrlPart::Query, Operator::ParamAbsent, "token"),
+    ])]);
+    assert_eq!(Some("present"), present.evaluate(&url("x.com", "/", "a=1&token=x")));
+    assert_eq!(None, present.evaluate(&url("x.com", "/", "a=1")));
+}
+
+#[test]
+fn param_conditions_match_the_named_param_value() {
+    let engine = RuleEngine::new(vec![
+        rule("product", 2, "product", vec![
+            cond(UrlPart::Query, Operator::StartsWith, "prod_").with_param("id"),
+        ]),
+        rule("tens", 1, "tens", vec![
+            cond(UrlPart::Query, Operator::EndsWith, "0").with_param("page"),
+        ]),
+        rule("no-debug", 0, "no-debug", vec![
+            neg_cond(UrlPart::Query, Operator::Equals, "1").with_param("debug"),
+        ]),
+    ]);
+
+    assert_eq!(Some("product"), engine.evaluate(&url("x.com", "/", "a=prod_x&id=prod_123")));
+    assert_eq!(Some("tens"), engine.evaluate(&url("x.com", "/", "id=item_1&page=20")));
+    assert_eq!(Some("product"), engine.evaluate(&url("x.com", "/", "id=1&id=prod_2&debug=1")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/", "pageid=prod_1&page=21&debug=1")));
+    // A missing param never matches, so its negation does.
+    assert_eq!(Some("no-debug"), engine.evaluate(&url("x.com", "/", "prod_=1")));
+    assert_eq!(None, engine.evaluate(&url("x.com", "/", "debug=1")));
+
+    let json = r#"[{"name":"bad","priority":1,"result":"x",
+      "conditions":[{"part":"path","operator":"equals","value":"1","param":"id"}]}]"#;
+    let err = RuleLoader::load_from_str(json).unwrap_err();
+    assert!(matches!(err, RuleEngineError::Validation(_)));
+}
+
+#[test]
+fn param_conditions_consider_every_value_of_a_repeated_key() {
+    let has_b = cond(UrlPart::Query, Operator::Equals, "b").with_param("tag");
+    let no_c = neg_cond(UrlPart::Query, Operator::Equals, "c").with_param("tag");
+    let no_a = neg_cond(UrlPart::Query, Operator::Equals, "a").with_param("tag");
+    let single = |c: Condition| RuleEngine::new(vec![rule("r", 1, "hit", vec![c])]);
+    let tags = url("x.com", "/", "tag=a&other=b&tag=b");
+
+    assert_eq!(Some("hit"), single(has_b.clone()).evaluate(&tags));
+    assert_eq!(Some("hit"), single(no_c.clone()).evaluate(&tags));
+    assert_eq!(None, single(no_a).evaluate(&tags));
+    assert_eq!(None, single(has_b.clone()).evaluate(&url("x.com", "/", "tag=a&other=b")));
+    assert_eq!(None, single(no_c.clone()).evaluate(&url("x.com", "/", "tag=b&tag=c")));
+
+    let both = RuleEngine::new(vec![rule("both", 1, "hit", vec![has_b, no_c])]);
+    assert_eq!(Some("hit"), both.evaluate(&tags));
+}
+
+#[test]
+fn decoded_param_conditions_split_before_decoding() {
+    let single = |c: Condition| RuleEngine::new(vec![rule("r", 1, "hit", vec![c])]);
+    let b_is_x = cond(UrlPart::Query, Operator::Equals, "x").with_param("b").with_decoded(true);
+    let q_full = cond(UrlPart::Query, Operator::Equals, "a&b=x").with_param("q").with_decoded(true);
+    let encoded_key = cond(UrlPart::Query, Operator::Equals, "1").with_param("k y").with_decoded(true);
+    let parsed = UrlParser::parse_with_decoded("https://x.com/?q=a%26b%3Dx&k%20y=1").unwrap();
+
+    assert_eq!(None, single(b_is_x).evaluate(&parsed));
+    assert_eq!(Some("hit"), single(q_full.clone()).evaluate(&parsed));
+    assert_eq!(Some("hit"), single(encoded_key).evaluate(&parsed));
+    // Without decoded parts the raw pairs are compared.
+    let raw = UrlParser::parse("https://x.com/?q=a%26b%3Dx").unwrap();
+    assert_eq!(None, single(q_full).evaluate(&raw));
+}
+
+fn oversized_query_rules() -> Vec<Rule> {
+    vec![
+        rule("needle", 3, "needle", vec![cond(UrlPart::Query, Operator::Contains, "needle")]),
+        rule("tail", 2, "tail", vec![cond(UrlPart::Query, Operator::EndsWith, "&end=1")]),
+        rule("head", 1, "head", vec![cond(UrlPart::Query, Operator::StartsWith, "start=1&")]),
+    ]
+}
+
+/// A 10MB query starting with `start=1&`, ending with `&end=1` and with
+/// `needle` only in the middle.
+fn ten_megabyte_query() -> String {
+    let filler = "x".repeat(5 * 1024 * 1024);
+    format!("start=1&{filler}needle{filler}&end=1")
+}
+
+#[test]
+fn truncated_oversized_part_keeps_head_and_tail() {
+    let query = ten_megabyte_query();
+    let long = url("x.com", "/", &query);
+    let engine = RuleEngine::new(oversized_query_rules());
+    assert_eq!(Some("needle"), engine.evaluate(&long));
+
+    let truncated = long.truncated(4096);
+    assert_eq!(4096, truncated.query.len());
+    assert!(truncated.query.starts_with("start=1&") && truncated.query.ends_with("&end=1"));
+    assert!(!truncated.query.contains("needle"));
+
+    let engine = RuleEngine::new(oversized_query_rules())
+        .with_max_part_len(4096, OversizedPart::Truncate);
+    assert_eq!(Some("tail"), engine.evaluate(&long));
+    let hits: Vec<&str> = engine.evaluate_all(&long).iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(vec!["tail", "head"], hits);
+    assert!(!engine.rejects(&long));
+    assert_eq!(Some("needle"), engine.evaluate(&url("x.com", "/", "start=1&needle")));
+}
+
+#[test]
+fn rejected_oversized_part_is_invalid() {
+    let query = ten_megabyte_query();
+    let long = url("x.com", "/", &query);
+    let engine = RuleEngine::new(oversized_query_rules())
+        .with_max_part_len(4096, OversizedPart::Reject);
+
+    assert!(engine.rejects(&long));
+    assert_eq!(None, engine.evaluate(&long));
+    assert!(engine.evaluate_all(&long).is_empty());
+    assert!(!engine.matches_any(&long));
+    assert_eq!(Some("head"), engine.evaluate(&url("x.com", "/", "start=1&a=b")));
+
+    let lines = vec![format!("https://x.com/?{query}"), "https://x.com/?start=1&a".to_string()];
+    let results = BatchProcessor::new(&engine).process_lines(&lines);
+    assert_eq!(MatchStatus::InvalidUrl, results[0].status);
+    assert_eq!("head", results[1].result);
+}
+
+#[test]
+fn rules_matching_value_finds_referencing_rules() {
+    let mut disabled =
+        rule("off", 1, "x", vec![cond(UrlPart::Host, Operator::HostSuffix, "google.com")]);
+    disabled.enabled = false;
+    let engine = RuleEngine::new(vec![
+        rule("pos", 3, "x", vec![cond(UrlPart::Host, Operator::Equals, "www.Google.com")]),
+        rule("other", 2, "x", vec![cond(UrlPart::Host, Operator::Equals, "bing.com")]),
+        rule("neg", 1, "x", vec![neg_cond(UrlPart::Host, Operator::EndsWith, ".google.com")]),
+        rule(
+            "any",
+            1,
+            "x",
+            vec![cond(UrlPart::Query, Operator::ContainsAny, "").with_values(["ref=google.com"])],
+        ),
+        disabled,
+    ]);
+
+    let names: Vec<&str> = engine
+        .rules_matching_value("GOOGLE.com")
+        .iter()
+        .map(|r| r.name.as_str())
+        .collect();
+    assert_eq!(vec!["pos", "neg", "any", "off"], names);
+    assert!(engine.rules_matching_value("yahoo").is_empty());
+}
+
+#[test]
+fn description_and_tags_survive_load_and_filter_by_tag() {
+    let json = r#"[
+        {"name": "ads", "priority": 2, "result": "blocked",
+         "description": "Known ad hosts", "tags": ["ads", "privacy"], "owner": "web-team",
+         "conditions": [{"part": "host", "operator": "host_suffix", "value": "ads.example"}]},
+        {"name": "trackers", "priority": 1, "result": "blocked", "tags": ["privacy"], "enabled": false,
+         "conditions": [{"part": "query", "operator": "contains", "value": "utm_"}]},
+        {"name": "home", "priority": 0, "result": "home",
+         "conditions": [{"part": "path", "operator": "equals", "value": "/"}]}
+    ]"#;
+    let rules = RuleLoader::load_from_str(json).unwrap();
+    assert_eq!(Some("Known ad hosts"), rules[0].description.as_deref());
+    assert_eq!(vec!["ads", "privacy"], rules[0].tags);
+    assert_eq!(None, rules[2].description);
+    assert!(rules[2].tags.is_empty());
+
+    let engine = RuleEngine::new(rules);
+    let names = |tag| engine.rules_with_tag(tag).iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(vec!["ads", "trackers"], names("privacy"));
+    assert_eq!(vec!["ads"], names("ads"));
+    assert!(names("Privacy").is_empty());
+    assert_eq!(Some("blocked"), engine.evaluate(&url("cdn.ads.example", "/", "")));
+}
+
+/// Unique per-process path so parallel test runs do not collide.
+fn temp_snapshot(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("rule-engine-{}-{}.bin", name, std::process::id()))
+}
+
+#[test]
+fn compiled_snapshot_round_trip_evaluates_identically() {
+    let rules = vec![
+        rule(
+            "sport-ca",
+            10,
+            "canada-sport",
+            vec![
+                cond(UrlPart::Host, Operator::EndsWith, ".ca"),
+                cond(UrlPart::Path, Operator::Contains, "sport"),
+            ],
+        ),
+        rule(
+            "api",
+            5,
+            "api",
+            vec![cond(UrlPart::Path, Operator::Glob, "/api/*/users")],
+        ),
+        rule(
+            "listed",
+            4,
+            "listed",
+            vec![cond(UrlPart::Host, Operator::InList, "a.com,b.com")],
+        ),
+        rule(
+            "example",
+            3,
+            "example",
+            vec![
+                cond(UrlPart::Host, Operator::HostSuffix, "example.com"),
+                neg_cond(UrlPart::Query, Operator::StartsWith, "debug"),
+            ],
+        ),
+        rule(
+            "html",
+            1,
+            "html",
+            vec![cond(UrlPart::File, Operator::Equals, "index.html")],
+        ),
+    ];
+    let built = RuleEngine::new(rules);
+    let path = temp_snapshot("round-trip");
+    built.save_compiled(&path).unwrap();
+    let loaded = RuleEngine::load_compiled(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let urls = [
+        url("news.ca", "/sport/hockey", ""),
+        url("x.com", "/api/v2/users", ""),
+        url("b.com", "/", ""),
+        url("www.example.com", "/", "q=1"),
+        url("www.example.com", "/", "debug=1"),
+        url("site.org", "/docs/index.html", ""),
+        url("nothing.net", "/", ""),
+    ];
+    for u in &urls {
+        assert_eq!(built.evaluate(u), loaded.evaluate(u));
+    }
+    assert_eq!(Some("canada-sport"), loaded.evaluate(&urls[0]));
+    assert_eq!(None, loaded.evaluate(&urls[6]));
+}
+
+#[test]
+fn load_compiled_rejects_non_snapshot_file() {
+    let path = temp_snapshot("garbage");
+    std::fs::write(&path, b"[{\"name\": \"not a snapshot\"}]").unwrap();
+    let err = RuleEngine::load_compiled(&path).err().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(matches!(err, RuleEngineError::Snapshot(_)));
+}
+
+#[test]
+fn binary_rules_round_trip() {
+    let rules = RuleLoader::load_from_str(INTEGRATION_RULES_JSON).unwrap();
+    let mut extra = rule(
+        "extra",
+        2,
+        "x",
+        vec![
+            cond(UrlPart::Path, Operator::Matches, "").with_bounds("/api", ".json"),
+            cond(UrlPart::Query, Operator::ContainsAny, "").with_values(["a", "b"]),
+        ],
+    )
+    .with_id("extra-1");
+    extra.enabled = false;
+    let rules: Vec<Rule> = rules.into_iter().chain([extra]).collect();
+
+    let path = temp_snapshot("rules-bin");
+    RuleLoader::save_bin(&rules, &path).unwrap();
+    let loaded = RuleLoader::load_bin(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(rules, loaded);
+}
+
+#[test]
+fn load_bin_rejects_foreign_and_truncated_files() {
+    let path = temp_snapshot("rules-bin-bad");
+    std::fs::write(&path, b"[]").unwrap();
+    assert!(matches!(RuleLoader::load_bin(&path), Err(RuleEngineError::Snapshot(_))));
+
+    let rules = RuleLoader::load_from_str(TEST_RULES_JSON).unwrap();
+    RuleLoader::save_bin(&rules, &path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    for cut in [4, 8, bytes.len() / 2, bytes.len() - 1] {
+        std::fs::write(&path, &bytes[..cut]).unwrap();
+        assert!(matches!(RuleLoader::load_bin(&path), Err(RuleEngineError::Snapshot(_))), "cut {}", cut);
+    }
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[4] = 99;
+    std::fs::write(&path, &wrong_version).unwrap();
+    let err = RuleLoader::load_bin(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.to_string().contains("version 99"));
+}
+
+// ====================================================================
+// BatchProcessorTest (5 tests)
+// ====================================================================
+
+#[test]
+fn processes_multiple_urls() {
+    let r1 = rule(
+        "ca-sport",
+        10,
+        "Canada Sport",
+        vec![
+            cond(UrlPart::Host, Operator::EndsWith, ".ca"),
+            cond(UrlPart::Path, Operator::Contains, "sport"),
+        ],
+    );
+    let r2 = rule(
+        "example-home",
+        5,
+        "Example Home",
+        vec![
+            cond(UrlPart::Host, Operator::Equals, "example.com"),
+            cond(UrlPart::Path, Operator::Equals, "/"),
+        ],
+    );
+
+    let engine = RuleEngine::new(vec![r1, r2]);
+    let processor = BatchProcessor::new(&engine);
+
+    let lines: Vec<String> = vec![
+        "https://shop.example.ca/category/sport/items".to_string(),
+        "https://example.com/".to_string(),
+        "https://other.org/page".to_string(),
+    ];
+
+    let results = processor.process_lines(&lines);
+
+    assert_eq!(3, results.len());
+    assert_eq!("Canada Sport", results[0].result);
+    assert_eq!("Example Home", results[1].result);
+    assert_eq!("NO_MATCH", results[2].result);
+}
+
+#[test]
+fn skips_blank_lines() {
+    let r = rule(
+        "r",
+        1,
+        "ok",
+        vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+    let processor = BatchProcessor::new(&engine);
+
+    let lines: Vec<String> = vec![
+        "https://x.com/".to_string(),
+        "".to_string(),
+        "  ".to_string(),
+        "https://x.com/page".to_string(),
+    ];
+    let results = processor.process_lines(&lines);
+    assert_eq!(2, results.len());
+}
+
+#[test]
+fn comment_prefix_skips_comment_lines() {
+    let r = rule(
+        "r",
+        1,
+        "ok",
+        vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    let lines: Vec<String> = [
+        "# header",
+        "https://x.com/first",
+        "",
+        "   # indented comment",
+        "https://y.com/second",
+        "  ",
+        "https://x.com/third#fragment",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    let results = BatchProcessor::new(&engine).with_comment_prefix("#").process_lines(&lines);
+    let urls: Vec<&str> = results.iter().map(|r| r.url.as_str()).collect();
+    assert_eq!(
+        vec![
+            "https://x.com/first",
+            "https://y.com/second",
+            "https://x.com/third#fragment"
+        ],
+        urls
+    );
+    let pooled = BatchProcessor::with_threads(&engine, 2).with_comment_prefix("#");
+    assert_eq!(results, pooled.process_lines(&lines));
+
+    // Without a prefix, comment lines are evaluated as URLs.
+    assert_eq!(5, BatchProcessor::new(&engine).process_lines(&lines).len());
+}
+
+#[test]
+fn handles_invalid_urls() {
+    let r = rule(
+        "r",
+        1,
+        "ok",
+        vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+    let processor = BatchProcessor::new(&engine);
+
+    let lines: Vec<String> = vec!["://bad-url".to_string()];
+    let results = processor.process_lines(&lines);
+    assert_eq!(1, results.len());
+    assert_eq!("INVALID_URL", results[0].result);
+    assert_eq!(MatchStatus::InvalidUrl, results[0].status);
+}
+
+#[test]
+fn empty_input_returns_empty_results() {
+    let engine = RuleEngine::new(vec![]);
+    let processor = BatchProcessor::new(&engine);
+    let results = processor.process_lines(&[]);
+    assert!(results.is_empty());
+}
+
+#[test]
+fn json_lines_round_trip() {
+    let r = rule(
+        "quote",
+        1,
+        "say \"hi\" \\ bye",
+        vec![cond(UrlPart::Host, Operator::Equals, "x.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+    let processor = BatchProcessor::new(&engine);
+
+    let lines: Vec<String> = vec![
+        "https://x.com/a\"b\\c".to_string(),
+        "https://y.com/".to_string(),
+        "://bad-url".to_string(),
+    ];
+    let records = processor.process_lines_json(&lines);
+    assert_eq!(3, records.len());
+
+    let parsed: Vec<serde_json::Value> = records
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!("https://x.com/a\"b\\c", parsed[0]["url"]);
+    assert_eq!("say \"hi\" \\ bye", parsed[0]["result"]);
+    assert_eq!("matched", parsed[0]["status"]);
+    assert_eq!("NO_MATCH", parsed[1]["result"]);
+    assert_eq!("no_match", parsed[1]["status"]);
+    assert_eq!("invalid_url", parsed[2]["status"]);
+    assert!(records.iter().all(|line| !line.contains('\n')));
+}
+
+#[test]
+fn csv_output_escapes_commas_and_quotes() {
+    let comma = rule(
+        "comma",
+        2,
+        "Sport, Canada",
+        vec![cond(UrlPart::Host, Operator::Equals, "a.ca")],
+    );
+    let quote = rule(
+        "quote",
+        1,
+        "the \"best\" shop",
+        vec![cond(UrlPart::Host, Operator::Equals, "b.com")],
+    );
+    let engine = RuleEngine::new(vec![comma, quote]);
+    let processor = BatchProcessor::new(&engine);
+
+    let lines: Vec<String> = vec![
+        "https://a.ca/x".to_string(),
+        "https://b.com/y".to_string(),
+        "https://c.org/z".to_string(),
+    ];
+    let results = processor.process_lines(&lines);
+    let mut out = Vec::new();
+    processor.write_csv(&results, &mut out).unwrap();
+
+    assert_eq!(
+        "url,result\r\n\
+         https://a.ca/x,\"Sport, Canada\"\r\n\
+         https://b.com/y,\"the \"\"best\"\" shop\"\r\n\
+         https://c.org/z,NO_MATCH\r\n",
+        String::from_utf8(out).unwrap()
+    );
+}
+
+#[test]
+fn parallel_processing_preserves_order() {
+    let r = rule(
+        "host-match",
+        1,
+        "matched",
+        vec![cond(UrlPart::Host, Operator::Equals, "example.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+    let processor = BatchProcessor::new(&engine);
+
+    let urls: Vec<String> = (0..10_000)
+        .map(|i| format!("https://example.com/page/{}", i))
+        .collect();
+
+    let results = processor.process_lines(&urls);
+
+    assert_eq!(urls.len(), results.len());
+    for (i, result) in results.iter().enumerate() {
+        assert_eq!(
+            format!("https://example.com/page/{}", i),
+            result.url,
+            "Result at index {} has wrong URL",
+            i
+        );
+        assert_eq!("matched", result.result);
+    }
+}
+
+#[test]
+fn process_file_with_progress_reports_every_chunk() {
+    let r = rule(
+        "host-match",
+        1,
+        "matched",
+        vec![cond(UrlPart::Host, Operator::Equals, "example.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+    let processor = BatchProcessor::new(&engine);
+
+    let mut content = String::new();
+    for i in 0..10_000 {
+        if i % 100 == 0 {
+            content.push('\n');
+        } else {
+            content.push_str(&format!("https://example.com/page/{}\n", i));
+        }
+    }
+    let path = std::env::temp_dir().join(format!("rule-engine-progress-{}.txt", std::process::id()));
+    std::fs::write(&path, &content).unwrap();
+
+    let mut reported = Vec::new();
+    let results = processor.process_file_with_progress(&path, 1_000, &mut |n| reported.push(n));
+    let mut uneven = Vec::new();
+    let uneven_results = processor.process_file_with_progress(&path, 3_000, &mut |n| uneven.push(n));
+    std::fs::remove_file(&path).unwrap();
+
+    let expected: Vec<usize> = (1..=10).map(|i| i * 1_000).collect();
+    assert_eq!(expected, reported);
+    let results = results.unwrap();
+    assert_eq!(9_900, results.len());
+    assert_eq!("https://example.com/page/1", results[0].url);
+    // The partial last chunk is reported too, ending on the line count.
+    assert_eq!(vec![3_000, 6_000, 9_000, 10_000], uneven);
+    assert_eq!(results, uneven_results.unwrap());
+}
+
+#[test]
+fn process_lines_iter_yields_process_lines_sequence() {
+    let engine = RuleEngine::new(vec![
+        rule("ca", 2, "canada", vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")]),
+        rule("sport", 1, "sport", vec![cond(UrlPart::Path, Operator::Contains, "sport")]),
+    ]);
+    let processor = BatchProcessor::new(&engine).with_comment_prefix("#");
+    let lines: Vec<String> = [
+        "https://news.ca/",
+        "# comment",
+        "https://example.com/sport",
+        "",
+        "://path",
+        "https://example.com/",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    let mut iter = processor.process_lines_iter(&lines);
+    assert_eq!(Some("canada".to_string()), iter.next().map(|r| r.result));
+    let rest: Vec<_> = iter.collect();
+    assert_eq!(processor.process_lines(&lines)[1..], rest[..]);
+}
+
+#[test]
+fn process_lines_detailed_reports_parse_errors_by_line() {
+    let r = rule(
+        "host-match",
+        1,
+        "matched",
+        vec![cond(UrlPart::Host, Operator::Equals, "example.com")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+    let processor = BatchProcessor::new(&engine).with_comment_prefix("#");
+
+    let lines: Vec<String> = [
+        "https://example.com/a",
+        "# comment",
+        "://path",
+        "",
+        "  https://[::1/x",
+        "https://other.com/",
+        "https://",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    let (results, errors) = processor.process_lines_detailed(&lines);
+
+    assert_eq!(processor.process_lines(&lines), results);
+    assert_eq!(
+        vec![
+            (2, UrlParseError::MissingHost { at: 3 }),
+            (4, UrlParseError::MalformedHost { at: 14 }),
+            (6, UrlParseError::MissingHost { at: 8 }),
+        ],
+        errors
+    );
+    // Offsets index the line as given, leading whitespace included.
+    assert_eq!("/x", &lines[4][errors[1].1.at().unwrap()..]);
+}
+
+#[test]
+fn dedicated_pool_matches_global_pool_output() {
+    let r = rule(
+        "even",
+        1,
+        "matched",
+        vec![cond(UrlPart::File, Operator::EndsWith, "0")],
+    );
+    let engine = RuleEngine::new(vec![r]);
+
+    let urls: Vec<String> = (0..10_000)
+        .map(|i| format!("https://example.com/page/{}", i))
+        .collect();
+
+    let expected = BatchProcessor::new(&engine).process_lines(&urls);
+    let actual = BatchProcessor::with_threads(&engine, 2).process_lines(&urls);
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn warmup_pool_reaches_every_worker() {
+    let engine = RuleEngine::new(vec![rule(
+        "ca",
+        1,
+        "canada",
+        vec![cond(UrlPart::Host, Operator::EndsWith, ".ca")],
+    )]);
+    let processor = BatchProcessor::with_threads(&engine, 3);
+
+    assert_eq!(3, processor.warmup_pool());
+    let results = processor.process_lines(&["https://cbc.ca/".to_string()]);
+    assert_eq!("canada", results[0].result);
+}
+
+// ====================================================================
+// AppTest (integration with test-rules.json)
+// ====================================================================
+
+const TEST_RULES_JSON: &str = include_str!("data/test-rules.json");
+
+#[test]
+fn integration_test_with_resource_files() {
+    let rules = RuleLoader::load_from_str(TEST_RULES_JSON).unwrap();
+    let engine = RuleEngine::new(rules);
+    let processor = BatchProcessor::new(&engine);
+
+    let lines: Vec<String> = vec![
+        "https://shop.example.ca/category/sport/items".to_string(),
+        "https://example.com/".to_string(),
+        "https://example.com/admin/panel".to_string(),
+        "https://example.com/user/profile".to_string(),
+        "https://news.example.ca/sport/hockey".to_string(),
+    ];
+
+    let results = processor.process_lines(&lines);
+
+    assert_eq!(5, results.len());
+    assert_eq!("Canada Sport", results[0].result);
+    assert_eq!("Example Home", results[1].result);
+    // /admin/panel: Example Home requires path=/, so doesn't match.
+    // Not Admin is negated starts_with /admin → fails.
+    assert_eq!("NO_MATCH", results[2].result);
+    assert_eq!("Not Admin", results[3].result);
+    assert_eq!("Canada Sport", results[4].result);
+}
+
+#[test]
+fn integration_all_matches_with_resource_files() {
+    let rules = RuleLoader::load_from_str(TEST_RULES_JSON).unwrap();
+    let engine = RuleEngine::new(rules);
+    let processor = BatchProcessor::new(&engine);
+
+    let lines: Vec<String> = vec![
+        "https://shop.example.ca/category/sport/items".to_string(),
+        "https://example.com/".to_string(),
+        "https://example.com/admin/panel".to_string(),
+        "://missing-host".to_string(),
+    ];
+
+    let results = processor.process_lines_all(&lines);
+
+    assert_eq!(4, results.len());
+    assert_eq!(vec!["Canada Sport", "Not Admin"], results[0].rules);
+    assert_eq!(vec!["Example Home", "Not Admin"], results[1].rules);
+    assert!(results[2].rules.is_empty());
+    assert_eq!(MatchStatus::NoMatch, results[2].status);
+    assert_eq!(MatchStatus::InvalidUrl, results[3].status);
+}
+
+mod generated {
+    include!("data/test_rules_matcher.rs");
+}
+
+#[test]
+fn generated_matcher_for_resource_rules_matches_engine() {
+    let rules = RuleLoader::load_from_str(TEST_RULES_JSON).unwrap();
+    let engine = RuleEngine::new(rules);
+
+    // The included matcher is the checked-in output; regenerate it by
+    // writing `generate_rust_matcher()` to data/test_rules_matcher.rs.
+    let source = engine.generate_rust_matcher();
+    assert_eq!(include_str!("data/test_rules_matcher.rs"), source);
+    for result in ["Canada Sport", "Example Home", "Not Admin"] {
+        assert!(source.contains(&format!("return Some({result:?});")));
+    }
+
+    for (host, path) in [
+        ("shop.example.ca", "/category/sport/items"),
+        ("example.com", "/"),
+        ("example.com", "/admin/panel"),
+        ("example.com", "/user/profile"),
+        ("example.ca", "/admin/sport"),
+    ] {
+        let u = url(host, path, "");
+        assert_eq!(engine.evaluate(&u), generated::match_url(&u), "{host}{path}");
+    }
+}
+
+mod generated_patterns {
+    include!("data/pattern_rules_matcher.rs");
+}
+
+#[test]
+fn generated_matcher_compiles_each_pattern_once() {
+    let engine = RuleEngine::new(vec![
+        rule("api", 3, "api", vec![
+            cond(UrlPart::Host, Operator::HostPattern, "api.*.example.com"),
+        ]),
+        rule("user-pages", 2, "user", vec![cond(UrlPart::Path, Operator::Glob, "/user/*/page?")]),
+        rule("not-user", 1, "other", vec![
+            neg_cond(UrlPart::Path, Operator::Glob, "/user/*/page?"),
+            cond(UrlPart::Host, Operator::HostPattern, "*.example.com"),
+        ]),
+    ]);
+
+    let source = engine.generate_rust_matcher();
+    assert_eq!(include_str!("data/pattern_rules_matcher.rs"), source);
+    // Patterns live in statics, declared once each, never built per call.
+    assert_eq!(3, source.matches("LazyLock::new").count());
+    let function = &source[source.find("pub fn match_url").unwrap()..];
+    assert!(!function.contains("::new("));
+
+    for (host, path) in [
+        ("api.eu.example.com", "/"),
+        ("www.example.com", "/user/ann/page1"),
+        ("www.example.com", "/user/ann"),
+        ("example.org", "/"),
+    ] {
+        let u = url(host, path, "");
+        assert_eq!(engine.evaluate(&u), generated_patterns::match_url(&u), "{host}{path}");
+    }
 }
 
 // ====================================================================